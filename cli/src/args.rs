@@ -0,0 +1,186 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Result;
+use dnt::External;
+use dnt::ModuleFormat;
+use dnt::ModuleSpecifier;
+use dnt::Shim;
+use dnt::SpecifierExtension;
+
+pub const HELP_TEXT: &str = "\
+Transforms a Deno module and its dependencies to canonical TypeScript.
+
+USAGE:
+  dnt <entry-point> --outDir <dir> [OPTIONS]
+
+OPTIONS:
+  --outDir <dir>
+      Directory to write the output files to.
+  --testEntryPoint <path>
+      Test module to also transform. Can be provided multiple times.
+  --specifierExtension <none|js|original|mjs|cjs>
+      Extension to use in rewritten specifiers. Defaults to js.
+  --moduleFormat <esm|dual>
+      Use dual to also output CommonJS modules. The ES modules are then
+      written to the esm sub directory and the CommonJS modules to the
+      cjs sub directory. Defaults to esm.
+  --shimPackageName <name>
+      Package to import the Deno shim from.
+  --localShim
+      Outputs a local shim module implementing the used Deno APIs instead
+      of importing a shim package.
+  --external <prefix>[=<replacement>]
+      Does not load or output modules whose specifier starts with the
+      prefix, optionally replacing the prefix. Can be provided multiple
+      times.
+  --noRemote
+      Errors instead of downloading remote modules.
+  -h, --help
+      Prints this help text.
+";
+
+pub struct Args {
+  pub entry_point: ModuleSpecifier,
+  pub test_entry_points: Vec<ModuleSpecifier>,
+  pub out_dir: PathBuf,
+  pub specifier_extension: SpecifierExtension,
+  pub module_format: ModuleFormat,
+  pub shim: Shim,
+  pub externals: Vec<External>,
+  pub no_remote: bool,
+}
+
+pub enum ParsedArgs {
+  Help,
+  Run(Args),
+}
+
+pub fn parse_args(
+  args: impl IntoIterator<Item = String>,
+  cwd: &Path,
+) -> Result<ParsedArgs> {
+  let mut args = args.into_iter();
+  let mut maybe_entry_point = None;
+  let mut test_entry_points = Vec::new();
+  let mut maybe_out_dir = None;
+  let mut specifier_extension = SpecifierExtension::default();
+  let mut module_format = ModuleFormat::Esm;
+  let mut shim_package_name = None;
+  let mut local_shim = false;
+  let mut externals = Vec::new();
+  let mut no_remote = false;
+
+  while let Some(arg) = args.next() {
+    if arg == "-h" || arg == "--help" {
+      return Ok(ParsedArgs::Help);
+    }
+    if !arg.starts_with("--") {
+      if maybe_entry_point.is_some() {
+        bail!("Unexpected argument: {}", arg);
+      }
+      maybe_entry_point = Some(parse_specifier(&arg, cwd)?);
+      continue;
+    }
+
+    // support both `--name value` and `--name=value`
+    let (name, mut maybe_value) = match arg.find('=') {
+      Some(index) => (&arg[..index], Some(arg[index + 1..].to_string())),
+      None => (arg.as_str(), None),
+    };
+    let mut take_value = || -> Result<String> {
+      match maybe_value.take().or_else(|| args.next()) {
+        Some(value) => Ok(value),
+        None => bail!("Missing value for {}.", name),
+      }
+    };
+    match name {
+      "--outDir" => maybe_out_dir = Some(cwd.join(take_value()?)),
+      "--testEntryPoint" => {
+        test_entry_points.push(parse_specifier(&take_value()?, cwd)?)
+      }
+      "--specifierExtension" => {
+        specifier_extension = match take_value()?.as_str() {
+          "none" => SpecifierExtension::None,
+          "js" => SpecifierExtension::Js,
+          "original" => SpecifierExtension::Original,
+          "mjs" => SpecifierExtension::Mjs,
+          "cjs" => SpecifierExtension::Cjs,
+          value => bail!("Invalid specifier extension: {}", value),
+        }
+      }
+      "--moduleFormat" => {
+        module_format = match take_value()?.as_str() {
+          "esm" => ModuleFormat::Esm,
+          "dual" => ModuleFormat::Dual,
+          value => bail!("Invalid module format: {}", value),
+        }
+      }
+      "--shimPackageName" => shim_package_name = Some(take_value()?),
+      "--localShim" => local_shim = true,
+      "--external" => {
+        let value = take_value()?;
+        externals.push(match value.find('=') {
+          Some(index) => External {
+            prefix: value[..index].to_string(),
+            replacement: Some(value[index + 1..].to_string()),
+          },
+          None => External {
+            prefix: value,
+            replacement: None,
+          },
+        });
+      }
+      "--noRemote" => no_remote = true,
+      _ => bail!("Unknown option: {}", name),
+    }
+  }
+
+  let entry_point = match maybe_entry_point {
+    Some(entry_point) => entry_point,
+    None => bail!(
+      "Please specify an entry point as the first argument (ex. `mod.ts`)."
+    ),
+  };
+  let out_dir = match maybe_out_dir {
+    Some(out_dir) => out_dir,
+    None => bail!("Please specify an output directory via --outDir."),
+  };
+  let shim = match (shim_package_name, local_shim) {
+    (Some(package_name), false) => Shim::Package(package_name),
+    (None, true) => Shim::Local,
+    (Some(_), true) => {
+      bail!("Cannot specify both --shimPackageName and --localShim.")
+    }
+    (None, false) => {
+      bail!("Please specify a shim via --shimPackageName or --localShim.")
+    }
+  };
+
+  Ok(ParsedArgs::Run(Args {
+    entry_point,
+    test_entry_points,
+    out_dir,
+    specifier_extension,
+    module_format,
+    shim,
+    externals,
+    no_remote,
+  }))
+}
+
+/// Parses a url or a file path relative to the current directory.
+fn parse_specifier(value: &str, cwd: &Path) -> Result<ModuleSpecifier> {
+  if let Ok(specifier) = ModuleSpecifier::parse(value) {
+    if matches!(specifier.scheme(), "file" | "http" | "https") {
+      return Ok(specifier);
+    }
+  }
+  match ModuleSpecifier::from_file_path(cwd.join(value)) {
+    Ok(specifier) => Ok(specifier),
+    Err(()) => bail!("Invalid module path: {}", value),
+  }
+}
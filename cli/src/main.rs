@@ -0,0 +1,140 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+mod args;
+
+use std::future::Future;
+use std::path::Path;
+use std::path::PathBuf;
+use std::pin::Pin;
+
+use anyhow::Context;
+use anyhow::Result;
+use args::parse_args;
+use args::Args;
+use args::ParsedArgs;
+use dnt::LoadResponse;
+use dnt::Loader;
+use dnt::ModuleFormat;
+use dnt::ModuleSpecifier;
+use dnt::OutputFile;
+use dnt::ShimImportStyle;
+use dnt::TestHarness;
+use dnt::TestOptions;
+use dnt::TransformOptions;
+use dnt::TypeDirectiveMode;
+
+/// Exit code for when the transform fails.
+const TRANSFORM_ERROR_EXIT_CODE: i32 = 1;
+/// Exit code for invalid command line arguments.
+const ARGS_ERROR_EXIT_CODE: i32 = 2;
+
+#[tokio::main]
+async fn main() {
+  let cwd = std::env::current_dir().expect("Could not get the current dir.");
+  let args = match parse_args(std::env::args().skip(1), &cwd) {
+    Ok(ParsedArgs::Help) => {
+      print!("{}", args::HELP_TEXT);
+      return;
+    }
+    Ok(ParsedArgs::Run(args)) => args,
+    Err(err) => {
+      eprintln!("error: {:#}\n\nRun `dnt --help` for usage.", err);
+      std::process::exit(ARGS_ERROR_EXIT_CODE);
+    }
+  };
+
+  if let Err(err) = run(args).await {
+    eprintln!("error: {:#}", err);
+    std::process::exit(TRANSFORM_ERROR_EXIT_CODE);
+  }
+}
+
+async fn run(args: Args) -> Result<()> {
+  let output = dnt::transform(TransformOptions {
+    entry_point: args.entry_point,
+    specifier_extension: args.specifier_extension,
+    module_format: args.module_format,
+    type_directives: TypeDirectiveMode::Rewrite,
+    shim: args.shim,
+    shim_import_style: ShimImportStyle::Namespace,
+    loader: Some(Box::new(CliLoader {
+      inner: dnt::DefaultLoader::new(),
+      no_remote: args.no_remote,
+    })),
+    test: if args.test_entry_points.is_empty() {
+      None
+    } else {
+      Some(TestOptions {
+        entry_points: args.test_entry_points,
+        harness: TestHarness::NodeTest,
+        assertion_module: None,
+      })
+    },
+    bundle: None,
+    types_overrides: Default::default(),
+    defines: Default::default(),
+    supported_deno_apis: None,
+    externals: args.externals,
+    visitors: Vec::new(),
+    third_party_notices: None,
+    source_headers: None,
+  })
+  .await?;
+
+  let (esm_dir, cjs_dir) = match args.module_format {
+    ModuleFormat::Esm => (args.out_dir.clone(), None),
+    ModuleFormat::Dual => {
+      (args.out_dir.join("esm"), Some(args.out_dir.join("cjs")))
+    }
+  };
+  for environment in [&output.main, &output.test] {
+    write_files(&esm_dir, &environment.files)?;
+    if let Some(cjs_dir) = &cjs_dir {
+      write_files(cjs_dir, &environment.cjs_files)?;
+    }
+  }
+  Ok(())
+}
+
+fn write_files(dir: &Path, files: &[OutputFile]) -> Result<()> {
+  for file in files {
+    let file_path = dir.join(&file.file_path);
+    if let Some(parent) = file_path.parent() {
+      std::fs::create_dir_all(parent).with_context(|| {
+        format!("Error creating directory {}", parent.display())
+      })?;
+    }
+    std::fs::write(&file_path, &file.file_text)
+      .with_context(|| format!("Error writing {}", file_path.display()))?;
+  }
+  Ok(())
+}
+
+/// Loader that is able to disallow downloading remote modules.
+struct CliLoader {
+  inner: dnt::DefaultLoader,
+  no_remote: bool,
+}
+
+impl Loader for CliLoader {
+  fn read_file(
+    &self,
+    file_path: PathBuf,
+  ) -> Pin<Box<dyn Future<Output = std::io::Result<String>> + 'static>> {
+    self.inner.read_file(file_path)
+  }
+
+  fn make_request(
+    &self,
+    url: ModuleSpecifier,
+  ) -> Pin<Box<dyn Future<Output = Result<LoadResponse>> + 'static>> {
+    if self.no_remote {
+      Box::pin(std::future::ready(Err(anyhow::anyhow!(
+        "Remote modules are not allowed with --noRemote: {}",
+        url
+      ))))
+    } else {
+      self.inner.make_request(url)
+    }
+  }
+}
@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Output;
+
+struct TempDir(PathBuf);
+
+impl TempDir {
+  fn new(name: &str) -> Self {
+    let path = std::env::temp_dir().join(format!(
+      "dnt_cli_{}_{}",
+      name,
+      std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    TempDir(path)
+  }
+
+  fn write(&self, file_path: &str, text: &str) {
+    std::fs::write(self.0.join(file_path), text).unwrap();
+  }
+
+  fn read(&self, file_path: &str) -> String {
+    std::fs::read_to_string(self.0.join(file_path)).unwrap()
+  }
+
+  fn run(&self, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_dnt"))
+      .args(args)
+      .current_dir(&self.0)
+      .output()
+      .unwrap()
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.0);
+  }
+}
+
+#[test]
+fn writes_output_files() {
+  let temp_dir = TempDir::new("writes_output_files");
+  temp_dir.write("mod.ts", "import './other.ts';\nDeno.cwd();");
+  temp_dir.write("other.ts", "export const value = 5;");
+
+  let output = temp_dir.run(&[
+    "mod.ts",
+    "--outDir",
+    "out",
+    "--shimPackageName=deno.ns",
+    "--noRemote",
+  ]);
+
+  assert_eq!(output.status.code(), Some(0));
+  assert_eq!(
+    temp_dir.read("out/mod.ts"),
+    concat!(
+      "import * as denoShim from \"deno.ns\";\n",
+      "import './other.js';\n",
+      "denoShim.Deno.cwd();",
+    )
+  );
+  assert_eq!(temp_dir.read("out/other.ts"), "export const value = 5;");
+}
+
+#[test]
+fn writes_dual_module_format() {
+  let temp_dir = TempDir::new("writes_dual_module_format");
+  temp_dir.write("mod.ts", "import './other.ts';");
+  temp_dir.write("other.ts", "");
+
+  let output = temp_dir.run(&[
+    "mod.ts",
+    "--outDir",
+    "out",
+    "--moduleFormat",
+    "dual",
+    "--specifierExtension",
+    "none",
+    "--localShim",
+  ]);
+
+  assert_eq!(output.status.code(), Some(0));
+  assert_eq!(temp_dir.read("out/esm/mod.ts"), "import './other';");
+  assert!(temp_dir
+    .read("out/cjs/mod.ts")
+    .contains("require('./other');"));
+}
+
+#[test]
+fn errors_on_remote_modules_with_no_remote() {
+  let temp_dir = TempDir::new("errors_on_remote_modules_with_no_remote");
+  temp_dir.write("mod.ts", "import 'https://deno.land/x/mod.ts';");
+
+  let output =
+    temp_dir.run(&["mod.ts", "--outDir", "out", "--localShim", "--noRemote"]);
+
+  assert_eq!(output.status.code(), Some(1));
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert!(stderr.contains(
+    "Remote modules are not allowed with --noRemote: https://deno.land/x/mod.ts"
+  ));
+}
+
+#[test]
+fn errors_on_invalid_args() {
+  let temp_dir = TempDir::new("errors_on_invalid_args");
+
+  let output = temp_dir.run(&["mod.ts"]);
+  assert_eq!(output.status.code(), Some(2));
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert!(stderr
+    .starts_with("error: Please specify an output directory via --outDir."));
+
+  let output = temp_dir.run(&["mod.ts", "--outDir", "out", "--unknown"]);
+  assert_eq!(output.status.code(), Some(2));
+
+  let output = temp_dir.run(&["mod.ts", "--outDir", "out"]);
+  assert_eq!(output.status.code(), Some(2));
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert!(stderr.starts_with(
+    "error: Please specify a shim via --shimPackageName or --localShim."
+  ));
+}
@@ -27,10 +27,10 @@ export async function emit(options: EmitOptions): Promise<EmitResult> {
     throw new Error("Please specify an outDir compiler option.");
   }
 
-  const outputFiles = await transform({
+  const { main: { files: outputFiles } } = await transform({
     entryPoint: options.entryPoint,
     shimPackageName: options.shimPackageName,
-    keepExtensions: shouldKeepExtensions(),
+    specifierExtension: getSpecifierExtension(),
   });
   const project = createProjectSync({
     compilerOptions: options.compilerOptions,
@@ -85,9 +85,10 @@ export async function emit(options: EmitOptions): Promise<EmitResult> {
     diagnostics: [],
   };
 
-  function shouldKeepExtensions() {
-    return options.compilerOptions.module === ts.ModuleKind.ES2015 ||
+  function getSpecifierExtension(): "js" | "none" {
+    const isEsm = options.compilerOptions.module === ts.ModuleKind.ES2015 ||
       options.compilerOptions.module === ts.ModuleKind.ES2020 ||
       options.compilerOptions.module === ts.ModuleKind.ESNext;
+    return isEsm ? "js" : "none";
   }
 }
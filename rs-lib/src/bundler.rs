@@ -0,0 +1,303 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use deno_ast::get_syntax;
+use deno_ast::swc::ast::KeyValueProp;
+use deno_ast::swc::ast::Module;
+use deno_ast::swc::ast::ModuleDecl;
+use deno_ast::swc::ast::ModuleItem;
+use deno_ast::swc::atoms::JsWord;
+use deno_ast::swc::bundler::Bundler;
+use deno_ast::swc::bundler::Hook;
+use deno_ast::swc::bundler::Load;
+use deno_ast::swc::bundler::ModuleData;
+use deno_ast::swc::bundler::ModuleRecord;
+use deno_ast::swc::bundler::ModuleType;
+use deno_ast::swc::bundler::Resolve;
+use deno_ast::swc::codegen::text_writer::JsWriter;
+use deno_ast::swc::codegen::Emitter;
+use deno_ast::swc::common::sync::Lrc;
+use deno_ast::swc::common::FileName;
+use deno_ast::swc::common::FilePathMapping;
+use deno_ast::swc::common::Globals;
+use deno_ast::swc::common::Mark;
+use deno_ast::swc::common::SourceFile;
+use deno_ast::swc::common::SourceMap;
+use deno_ast::swc::common::Span;
+use deno_ast::swc::common::GLOBALS;
+use deno_ast::swc::parser::lexer::Lexer;
+use deno_ast::swc::parser::Parser;
+use deno_ast::swc::parser::StringInput;
+use deno_ast::swc::transforms::fixer;
+use deno_ast::swc::transforms::resolver_with_mark;
+use deno_ast::swc::transforms::typescript;
+use deno_ast::swc::visit::FoldWith;
+use deno_ast::MediaType;
+
+use crate::OutputFile;
+use crate::OutputFileKind;
+use crate::SpecifierExtension;
+
+pub struct BundleParams<'a> {
+  pub files: &'a [OutputFile],
+  pub entry_point: &'a Path,
+  /// Extension used in the specifiers of the output files.
+  pub specifier_extension: SpecifierExtension,
+  pub shim_package_name: Option<&'a str>,
+  pub keep_bare_externals: bool,
+}
+
+/// Bundles the provided ES module output files into a single
+/// JavaScript module. Declaration files are not included in the bundle.
+pub fn bundle(params: BundleParams) -> Result<OutputFile> {
+  let cm: Lrc<SourceMap> = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+  let globals = Globals::new();
+  let mut modules = HashMap::new();
+  let mut externals = Vec::new();
+
+  GLOBALS.set(&globals, || -> Result<()> {
+    for file in params.files {
+      if file.kind == OutputFileKind::Declaration {
+        continue;
+      }
+      let (source_file, module) = parse_and_strip_types(&cm, file)?;
+      for src in get_module_srcs(&module) {
+        if !is_relative_specifier(&src) && !externals.contains(&src) {
+          if !params.keep_bare_externals
+            && Some(src.as_str()) != params.shim_package_name
+          {
+            anyhow::bail!(
+              "Could not bundle the bare specifier \"{}\" in {}.",
+              src,
+              file.file_path.display(),
+            );
+          }
+          externals.push(src);
+        }
+      }
+      modules.insert(file.file_path.clone(), (source_file, module));
+    }
+    Ok(())
+  })?;
+
+  // the specifiers were rewritten to the specifier paths of the output
+  // files, so resolve them the same way
+  let mut file_paths = modules.keys().collect::<Vec<_>>();
+  file_paths.sort();
+  let mut specifier_paths = HashMap::new();
+  for file_path in file_paths {
+    let specifier_path =
+      normalize_path(&params.specifier_extension.get_specifier_path(file_path));
+    if let Some(other_file_path) =
+      specifier_paths.insert(specifier_path.clone(), file_path.clone())
+    {
+      anyhow::bail!(
+        "Could not bundle {} and {} because both are imported as {}.",
+        other_file_path.display(),
+        file_path.display(),
+        specifier_path.display(),
+      );
+    }
+  }
+  let loader = BundleLoader {
+    modules: RefCell::new(modules),
+  };
+  let resolver = BundleResolver { specifier_paths };
+  let config = deno_ast::swc::bundler::Config {
+    module: ModuleType::Es,
+    external_modules: externals
+      .iter()
+      .map(|e| JsWord::from(e.as_str()))
+      .collect(),
+    ..Default::default()
+  };
+  let bundler = Bundler::new(
+    &globals,
+    cm.clone(),
+    loader,
+    resolver,
+    config,
+    Box::new(BundleHook),
+  );
+  let mut entries = HashMap::new();
+  entries.insert(
+    "bundle".to_string(),
+    FileName::Real(params.entry_point.to_path_buf()),
+  );
+  let mut output = bundler
+    .bundle(entries)
+    .context("Unable to output during bundling.")?;
+  let module = hoist_imports(output.remove(0).module);
+
+  let mut buf = Vec::new();
+  {
+    let mut emitter = Emitter {
+      cfg: deno_ast::swc::codegen::Config { minify: false },
+      cm: cm.clone(),
+      comments: None,
+      wr: Box::new(JsWriter::new(cm, "\n", &mut buf, None)),
+    };
+    emitter
+      .emit_module(&module)
+      .context("Unable to emit during bundling.")?;
+  }
+
+  Ok(OutputFile {
+    file_path: params.entry_point.with_extension("js"),
+    file_text: String::from_utf8(buf)
+      .context("Emitted bundle is an invalid string.")?,
+    media_type: MediaType::JavaScript,
+    kind: OutputFileKind::Local,
+    original_specifier: None,
+  })
+}
+
+/// Moves all the import declarations (ex. the Deno shim) to the top
+/// of the bundle.
+fn hoist_imports(mut module: Module) -> Module {
+  let (imports, others): (Vec<_>, Vec<_>) =
+    module.body.into_iter().partition(|item| {
+      matches!(item, ModuleItem::ModuleDecl(ModuleDecl::Import(_)))
+    });
+  module.body = imports.into_iter().chain(others).collect();
+  module
+}
+
+fn parse_and_strip_types(
+  cm: &Lrc<SourceMap>,
+  file: &OutputFile,
+) -> Result<(Lrc<SourceFile>, Module)> {
+  let media_type = file.media_type;
+  let source_file = cm.new_source_file(
+    FileName::Real(file.file_path.clone()),
+    file.file_text.clone(),
+  );
+  let lexer = Lexer::new(
+    get_syntax(media_type),
+    Default::default(),
+    StringInput::from(&*source_file),
+    None,
+  );
+  let mut parser = Parser::new_from(lexer);
+  let module = parser.parse_module().map_err(|err| {
+    anyhow::anyhow!(
+      "Error parsing {} for bundling: {:?}",
+      file.file_path.display(),
+      err.kind()
+    )
+  })?;
+  let top_level_mark = Mark::fresh(Mark::root());
+  let module = module
+    .fold_with(&mut resolver_with_mark(top_level_mark))
+    .fold_with(&mut typescript::strip())
+    .fold_with(&mut fixer(None));
+  Ok((source_file, module))
+}
+
+fn get_module_srcs(module: &Module) -> Vec<String> {
+  module
+    .body
+    .iter()
+    .filter_map(|item| match item {
+      ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) => {
+        Some(&import_decl.src)
+      }
+      ModuleItem::ModuleDecl(ModuleDecl::ExportAll(export_all)) => {
+        Some(&export_all.src)
+      }
+      ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(named_export)) => {
+        named_export.src.as_ref()
+      }
+      _ => None,
+    })
+    .map(|src| src.value.to_string())
+    .collect()
+}
+
+fn is_relative_specifier(specifier: &str) -> bool {
+  specifier.starts_with("./") || specifier.starts_with("../")
+}
+
+struct BundleLoader {
+  modules: RefCell<HashMap<PathBuf, (Lrc<SourceFile>, Module)>>,
+}
+
+impl Load for BundleLoader {
+  fn load(&self, file_name: &FileName) -> Result<ModuleData> {
+    let file_path = match file_name {
+      FileName::Real(file_path) => file_path,
+      _ => unreachable!(
+        "Received request for unsupported filename {:?}",
+        file_name
+      ),
+    };
+    match self.modules.borrow_mut().remove(file_path) {
+      Some((fm, module)) => Ok(ModuleData {
+        fm,
+        module,
+        helpers: Default::default(),
+      }),
+      None => Err(anyhow::anyhow!(
+        "Programming error. Could not find module to bundle: {}",
+        file_path.display()
+      )),
+    }
+  }
+}
+
+struct BundleResolver {
+  /// Output file paths by the path used to import them.
+  specifier_paths: HashMap<PathBuf, PathBuf>,
+}
+
+impl Resolve for BundleResolver {
+  fn resolve(&self, base: &FileName, specifier: &str) -> Result<FileName> {
+    let base = match base {
+      FileName::Real(base) => base,
+      _ => unreachable!("Received request for unsupported filename {:?}", base),
+    };
+    let file_path = normalize_path(&base.parent().unwrap().join(specifier));
+    match self.specifier_paths.get(&file_path) {
+      Some(file_path) => Ok(FileName::Real(file_path.clone())),
+      None => Err(anyhow::anyhow!(
+        "Could not resolve \"{}\" from {} when bundling.",
+        specifier,
+        base.display()
+      )),
+    }
+  }
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+  let mut result = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir => {
+        result.pop();
+      }
+      _ => result.push(component),
+    }
+  }
+  result
+}
+
+struct BundleHook;
+
+impl Hook for BundleHook {
+  fn get_import_meta_props(
+    &self,
+    _span: Span,
+    _module_record: &ModuleRecord,
+  ) -> Result<Vec<KeyValueProp>> {
+    // the bundle does not provide any import.meta properties
+    Ok(Vec::new())
+  }
+}
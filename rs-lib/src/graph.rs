@@ -0,0 +1,74 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use deno_ast::ModuleSpecifier;
+use deno_graph::Module;
+use deno_graph::ModuleGraphError;
+use deno_graph::Resolved;
+
+use crate::loader::SourceLoader;
+use crate::parser::CapturingSourceParser;
+
+/// Module graphs for one or more roots that share a loader.
+#[derive(Default)]
+pub struct ModuleGraph {
+  graphs: Vec<deno_graph::ModuleGraph>,
+}
+
+impl ModuleGraph {
+  pub async fn add_root(
+    &mut self,
+    root: ModuleSpecifier,
+    loader: &mut SourceLoader,
+    source_parser: &CapturingSourceParser,
+  ) {
+    let graph =
+      deno_graph::create_graph(root, loader, None, None, Some(source_parser))
+        .await;
+    self.graphs.push(graph);
+  }
+
+  pub fn get(&self, specifier: &ModuleSpecifier) -> Option<&Module> {
+    self.graphs.iter().find_map(|graph| graph.get(specifier))
+  }
+
+  pub fn try_get(
+    &self,
+    specifier: &ModuleSpecifier,
+  ) -> Result<Option<&Module>, ModuleGraphError> {
+    for graph in self.graphs.iter() {
+      if let Some(module) = graph.try_get(specifier)? {
+        return Ok(Some(module));
+      }
+    }
+    Ok(None)
+  }
+
+  /// Resolves any redirects of the provided specifier.
+  pub fn resolve(&self, specifier: &ModuleSpecifier) -> ModuleSpecifier {
+    self
+      .graphs
+      .iter()
+      .map(|graph| graph.resolve(specifier))
+      .find(|resolved| resolved != specifier)
+      .unwrap_or_else(|| specifier.clone())
+  }
+
+  /// Resolves the dependency of the referrer, falling back to its type
+  /// dependency for type only imports (ex. `import("./mod.ts").Type`).
+  pub fn resolve_dependency(
+    &self,
+    value: &str,
+    referrer: &ModuleSpecifier,
+  ) -> Option<ModuleSpecifier> {
+    self.graphs.iter().find_map(|graph| {
+      let module = graph.get(referrer)?;
+      if let Some(specifier) = graph.resolve_dependency(value, referrer) {
+        return Some(specifier.clone());
+      }
+      match &module.dependencies.get(value)?.maybe_type {
+        Resolved::Specifier(specifier, _) => Some(graph.resolve(specifier)),
+        _ => None,
+      }
+    })
+  }
+}
@@ -0,0 +1,166 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::OutputDependencyKind;
+use crate::OutputGraph;
+
+struct GraphReport<'a> {
+  /// Module specifiers along with their output file path. Externals
+  /// do not have an output file path.
+  nodes: BTreeMap<&'a str, Option<&'a PathBuf>>,
+  edges: BTreeSet<(&'a str, &'a str, &'static str)>,
+}
+
+impl<'a> GraphReport<'a> {
+  fn new(graph: &'a OutputGraph) -> Self {
+    let mut nodes = graph
+      .mappings
+      .iter()
+      .map(|(specifier, file_path)| (specifier.as_str(), Some(file_path)))
+      .collect::<BTreeMap<_, _>>();
+    let mut edges = BTreeSet::new();
+    for module in graph.modules.iter() {
+      for dependency in module.dependencies.iter() {
+        if let Some(resolved) = dependency.resolved_specifier.as_ref() {
+          nodes.entry(resolved.as_str()).or_insert(None);
+          edges.insert((
+            module.specifier.as_str(),
+            resolved.as_str(),
+            get_kind_text(dependency.kind),
+          ));
+        }
+      }
+    }
+    for types in graph.types.iter() {
+      edges.insert((
+        types.specifier.as_str(),
+        types.types_specifier.as_str(),
+        get_kind_text(OutputDependencyKind::Types),
+      ));
+    }
+    GraphReport { nodes, edges }
+  }
+}
+
+/// Renders the graph as JSON with a node for every module, labelled by
+/// its original specifier and output file path, and the edges between them.
+pub fn get_graph_json_text(graph: &OutputGraph) -> String {
+  let report = GraphReport::new(graph);
+  let nodes = report
+    .nodes
+    .iter()
+    .map(|(specifier, maybe_file_path)| {
+      format!(
+        "    {{ \"specifier\": {}, \"filePath\": {} }}",
+        get_json_string(specifier),
+        match maybe_file_path {
+          Some(file_path) => get_json_string(&get_display_path(file_path)),
+          None => "null".to_string(),
+        },
+      )
+    })
+    .collect::<Vec<_>>();
+  let edges = report
+    .edges
+    .iter()
+    .map(|(from, to, kind)| {
+      format!(
+        "    {{ \"from\": {}, \"to\": {}, \"kind\": \"{}\" }}",
+        get_json_string(from),
+        get_json_string(to),
+        kind,
+      )
+    })
+    .collect::<Vec<_>>();
+  format!(
+    "{{\n  \"nodes\": [{}],\n  \"edges\": [{}]\n}}\n",
+    get_json_array_items(nodes),
+    get_json_array_items(edges),
+  )
+}
+
+/// Renders the graph in the Graphviz DOT language. Dynamic imports are
+/// drawn dashed and type dependencies dotted.
+pub fn get_graph_dot_text(graph: &OutputGraph) -> String {
+  let report = GraphReport::new(graph);
+  let mut text = String::from("digraph {\n");
+  for (specifier, maybe_file_path) in report.nodes.iter() {
+    let label = match maybe_file_path {
+      Some(file_path) => {
+        format!("{}\n{}", specifier, get_display_path(file_path))
+      }
+      None => format!("{}\n(external)", specifier),
+    };
+    text.push_str(&format!(
+      "  {} [label={}];\n",
+      get_dot_string(specifier),
+      get_dot_string(&label),
+    ));
+  }
+  for (from, to, kind) in report.edges.iter() {
+    let attributes = match *kind {
+      "dynamic" => " [style=dashed, label=\"dynamic\"]",
+      "types" => " [style=dotted, label=\"types\"]",
+      _ => "",
+    };
+    text.push_str(&format!(
+      "  {} -> {}{};\n",
+      get_dot_string(from),
+      get_dot_string(to),
+      attributes,
+    ));
+  }
+  text.push_str("}\n");
+  text
+}
+
+fn get_kind_text(kind: OutputDependencyKind) -> &'static str {
+  match kind {
+    OutputDependencyKind::Static => "static",
+    OutputDependencyKind::Dynamic => "dynamic",
+    OutputDependencyKind::Types => "types",
+  }
+}
+
+fn get_display_path(file_path: &Path) -> String {
+  file_path.to_string_lossy().replace("\\", "/")
+}
+
+fn get_json_array_items(items: Vec<String>) -> String {
+  if items.is_empty() {
+    String::new()
+  } else {
+    format!("\n{}\n  ", items.join(",\n"))
+  }
+}
+
+fn get_json_string(value: &str) -> String {
+  let mut text = String::from("\"");
+  for c in value.chars() {
+    match c {
+      '"' => text.push_str("\\\""),
+      '\\' => text.push_str("\\\\"),
+      '\n' => text.push_str("\\n"),
+      '\r' => text.push_str("\\r"),
+      '\t' => text.push_str("\\t"),
+      c if (c as u32) < 0x20 => text.push_str(&format!("\\u{:04x}", c as u32)),
+      c => text.push(c),
+    }
+  }
+  text.push('"');
+  text
+}
+
+fn get_dot_string(value: &str) -> String {
+  format!(
+    "\"{}\"",
+    value
+      .replace("\\", "\\\\")
+      .replace("\"", "\\\"")
+      .replace("\n", "\\n")
+  )
+}
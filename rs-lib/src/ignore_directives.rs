@@ -0,0 +1,58 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use std::ops::Range;
+
+use deno_ast::swc::common::comments::Comment;
+use deno_ast::swc::common::BytePos;
+
+/// Ignore directives found in the comments of a module.
+///
+/// * `// dnt-ignore-file` - Ignores the entire module.
+/// * `// dnt-ignore-next-line` - Ignores the line after the comment.
+pub struct IgnoreDirectives {
+  is_file_ignored: bool,
+  ignored_lines: Vec<Range<usize>>,
+}
+
+impl IgnoreDirectives {
+  pub fn new(comments: &[Comment], source_text: &str) -> Self {
+    let mut is_file_ignored = false;
+    let mut ignored_lines = Vec::new();
+    for comment in comments {
+      match comment.text.trim() {
+        "dnt-ignore-file" => is_file_ignored = true,
+        "dnt-ignore-next-line" => {
+          if let Some(line) = get_next_line(source_text, comment.span.hi) {
+            ignored_lines.push(line);
+          }
+        }
+        _ => {}
+      }
+    }
+    IgnoreDirectives {
+      is_file_ignored,
+      ignored_lines,
+    }
+  }
+
+  pub fn is_file_ignored(&self) -> bool {
+    self.is_file_ignored
+  }
+
+  /// Gets if the provided position is ignored by either directive.
+  pub fn is_ignored(&self, pos: BytePos) -> bool {
+    let pos = pos.0 as usize;
+    self.is_file_ignored
+      || self.ignored_lines.iter().any(|line| line.contains(&pos))
+  }
+}
+
+fn get_next_line(source_text: &str, pos: BytePos) -> Option<Range<usize>> {
+  let pos = pos.0 as usize;
+  let start = pos + source_text[pos..].find('\n')? + 1;
+  let end = source_text[start..]
+    .find('\n')
+    .map(|i| start + i)
+    .unwrap_or_else(|| source_text.len());
+  Some(start..end)
+}
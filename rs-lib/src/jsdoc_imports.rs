@@ -0,0 +1,80 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use deno_ast::swc::common::comments::Comment;
+use deno_ast::swc::common::comments::CommentKind;
+use deno_ast::swc::common::BytePos;
+use deno_ast::swc::common::Span;
+use deno_ast::ModuleSpecifier;
+use regex::Regex;
+
+lazy_static! {
+  static ref IMPORT_TYPE_RE: Regex =
+    Regex::new(r#"import\(\s*["']([^"']+)["']\s*\)"#).unwrap();
+}
+
+/// An import type found in a JSDoc comment
+/// (ex. `/** @type {import("./mod.ts").Options} */`).
+pub struct JsDocImport {
+  pub value: String,
+  /// Span of the text within the quotes.
+  pub span: Span,
+}
+
+/// Gets the import types found in the JSDoc comments of a module.
+///
+/// Only relative specifiers and `http(s):`/`file:` urls are returned, and
+/// imports in `@example` sections are ignored.
+pub fn get_jsdoc_imports(comments: &[Comment]) -> Vec<JsDocImport> {
+  let mut imports = Vec::new();
+  for comment in comments {
+    if comment.kind != CommentKind::Block || !comment.text.starts_with('*') {
+      continue;
+    }
+
+    // skip over the leading `/*`
+    let text_start = comment.span.lo + BytePos(2);
+    let mut line_start = 0;
+    let mut is_example = false;
+    for line in comment.text.split('\n') {
+      let line_text = line.trim_start().trim_start_matches('*').trim_start();
+      if line_text.starts_with('@') {
+        is_example = line_text.starts_with("@example");
+      }
+      if !is_example {
+        for captures in IMPORT_TYPE_RE.captures_iter(line) {
+          let value = captures.get(1).unwrap();
+          if !is_supported_specifier(value.as_str()) {
+            continue;
+          }
+          let lo = text_start + BytePos((line_start + value.start()) as u32);
+          imports.push(JsDocImport {
+            value: value.as_str().to_string(),
+            span: Span::new(
+              lo,
+              lo + BytePos(value.as_str().len() as u32),
+              Default::default(),
+            ),
+          });
+        }
+      }
+      line_start += line.len() + 1;
+    }
+  }
+  imports
+}
+
+/// Gets if the specifier is relative or a url the transform is able to
+/// load. Bare specifiers (ex. `express`) and other schemes (ex. `node:fs`)
+/// are left as-is.
+fn is_supported_specifier(value: &str) -> bool {
+  if value.starts_with("./")
+    || value.starts_with("../")
+    || value.starts_with('/')
+  {
+    return true;
+  }
+  match ModuleSpecifier::parse(value) {
+    Ok(specifier) => matches!(specifier.scheme(), "http" | "https" | "file"),
+    Err(_) => false,
+  }
+}
@@ -1,78 +1,706 @@
 // Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
 
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::path::Path;
 use std::path::PathBuf;
 
+use anyhow::Context;
 use anyhow::Result;
-use deno_graph::create_graph;
-use deno_graph::ModuleGraph;
+use deno_ast::MediaType;
+use deno_ast::ParsedSource;
 use deno_graph::Resolved;
 #[macro_use]
 extern crate lazy_static;
 
-use loader::SourceLoader;
-use mappings::Mappings;
+use graph::ModuleGraph;
+use ignore_directives::IgnoreDirectives;
+use jsdoc_imports::get_jsdoc_imports;
+use loader::LoaderSpecifiers;
 use mappings::Specifiers;
 use text_changes::apply_text_changes;
+use utils::get_extension;
+use utils::get_line_and_column_display;
+use utils::get_relative_specifier;
+use utils::get_source_header_text_change;
+use utils::strip_extension;
+use utils::with_extension;
+use visitors::get_cjs_text_changes;
 use visitors::get_deno_global_text_changes;
 use visitors::get_module_specifier_text_changes;
+use visitors::get_type_directive_text_changes;
+use visitors::GetCjsTextChangesParams;
 use visitors::GetDenoGlobalTextChangesParams;
 use visitors::GetModuleSpecifierTextChangesParams;
+use visitors::GetTypeDirectiveTextChangesParams;
 
+pub use deno_ast;
 pub use deno_ast::ModuleSpecifier;
+pub use graph_report::get_graph_dot_text;
+pub use graph_report::get_graph_json_text;
+#[cfg(feature = "tokio-loader")]
+pub use loader::DefaultLoader;
 pub use loader::LoadResponse;
 pub use loader::Loader;
+pub use mappings::Mappings;
+pub use text_changes::TextChange;
+pub use visitors::TextChangeVisitor;
+pub use visitors::TextChangeVisitorParams;
 
+mod bundler;
+mod graph;
+mod graph_report;
+mod ignore_directives;
+mod jsdoc_imports;
+mod licenses;
 mod loader;
 mod mappings;
 mod parser;
+mod shims;
 mod text_changes;
 mod utils;
 mod visitors;
 
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFileKind {
+  /// A local module or a module generated by the transform
+  /// (ex. the local shim).
+  Local,
+  /// A remote module output to the `deps` directory.
+  Remote,
+  /// A declaration file.
+  Declaration,
+}
+
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
 #[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
 #[derive(Debug, PartialEq)]
 pub struct OutputFile {
   pub file_path: PathBuf,
   pub file_text: String,
+  /// Serialized as the name of the media type (ex. `"TypeScript"`).
+  #[cfg_attr(
+    feature = "serialization",
+    serde(serialize_with = "serialize_media_type")
+  )]
+  pub media_type: MediaType,
+  pub kind: OutputFileKind,
+  /// Specifier of the module the file was output for. This is `None`
+  /// for files generated by the transform (ex. bundles).
+  pub original_specifier: Option<String>,
+}
+
+#[cfg(feature = "serialization")]
+fn serialize_media_type<S: serde::Serializer>(
+  media_type: &MediaType,
+  serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+  // use the variant name so the output doesn't depend on how deno_ast
+  // serializes the media type
+  serializer.serialize_str(&format!("{:?}", media_type))
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, PartialEq)]
+pub struct TransformOutput {
+  pub main: TransformOutputEnvironment,
+  /// Modules only depended on by the test entry points.
+  pub test: TransformOutputEnvironment,
+  /// The `Deno` APIs used by each module that reference the shim.
+  pub deno_api_usage: Vec<DenoApiUsage>,
+  /// The output modules and how they depend on each other.
+  pub graph: OutputGraph,
+  /// License information of the remote modules. This is empty unless
+  /// the third party notices are output.
+  pub third_party_licenses: Vec<RemoteLicense>,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteLicense {
+  /// Root url of the remote modules (ex. `https://deno.land/x/mod@1.0.0/`).
+  pub root: String,
+  /// Output directory of the remote modules (ex. `deps/0`).
+  pub dir_path: PathBuf,
+  /// License comments found at the top of the remote modules.
+  pub comments: Vec<LicenseComment>,
+  /// Text of the closest `LICENSE` file at or above the root url within
+  /// its package, when fetched and found.
+  pub license_file_text: Option<String>,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LicenseComment {
+  pub specifier: String,
+  pub text: String,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputGraph {
+  /// Output file path of each module specifier.
+  pub mappings: BTreeMap<String, PathBuf>,
+  /// The transformed modules of both the main and test environments.
+  pub modules: Vec<OutputModule>,
+  /// Modules along with the declaration file that provides their types.
+  pub types: Vec<OutputTypesDependency>,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputModule {
+  pub specifier: String,
+  pub file_path: PathBuf,
+  pub dependencies: Vec<OutputDependency>,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputDependency {
+  /// Specifier text found in the original source.
+  pub specifier: String,
+  /// Specifier text in the output (ex. `./deps/0/mod.js`).
+  pub rewritten_specifier: String,
+  /// Module the specifier resolved to. This is `None` for externals
+  /// that could not be resolved (ex. bare specifiers).
+  pub resolved_specifier: Option<String>,
+  pub kind: OutputDependencyKind,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputDependencyKind {
+  /// Import or export declaration.
+  Static,
+  /// Statically analyzable dynamic import (ex. `import("./mod.ts")`).
+  Dynamic,
+  /// Type only import (ex. `import type { Type } from "./mod.ts"` or
+  /// `import("./mod.ts").Type`).
+  Types,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputTypesDependency {
+  pub specifier: String,
+  pub types_specifier: String,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DenoApiUsage {
+  pub specifier: String,
+  pub file_path: PathBuf,
+  pub members: Vec<DenoMemberUsage>,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DenoMemberUsage {
+  /// Member expression path (ex. `Deno.readTextFile`).
+  pub path: String,
+  /// One-based line number.
+  pub line: usize,
+  /// One-based column number.
+  pub column: usize,
+}
+
+impl DenoMemberUsage {
+  fn is_supported(&self, supported_apis: &[String]) -> bool {
+    supported_apis.iter().any(|api| {
+      self.path == *api
+        || (self.path.starts_with(api.as_str())
+          && self.path[api.len()..].starts_with('.'))
+    })
+  }
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, PartialEq, Default)]
+pub struct TransformOutputEnvironment {
+  pub files: Vec<OutputFile>,
+  /// CommonJS versions of `files` at the same file paths. This is
+  /// empty unless outputting the `ModuleFormat::Dual` format.
+  pub cjs_files: Vec<OutputFile>,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModuleFormat {
+  /// Outputs ES modules.
+  Esm,
+  /// Outputs ES modules along with a mirrored set of CommonJS modules.
+  Dual,
+}
+
+/// Extension used in the rewritten specifiers of local and remote modules.
+#[cfg_attr(feature = "serialization", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpecifierExtension {
+  /// Removes the extension (ex. `./mod.ts` -> `./mod`).
+  None,
+  /// Uses the extension of the emitted JavaScript (ex. `./mod.ts` ->
+  /// `./mod.js` and `./mod.mts` -> `./mod.mjs`).
+  Js,
+  /// Keeps the extension of the output file (ex. `./mod.ts`).
+  Original,
+  /// Uses `.mjs`. CommonJS output uses `.cjs` instead.
+  ///
+  /// The output files are given matching extensions (ex. `mod.ts` ->
+  /// `mod.mts`), so only TypeScript, JavaScript and declaration files
+  /// are supported.
+  Mjs,
+  /// Uses `.cjs`. The output files are given matching extensions
+  /// (ex. `mod.ts` -> `mod.cts`) like with `Mjs`.
+  Cjs,
+}
+
+impl Default for SpecifierExtension {
+  fn default() -> Self {
+    SpecifierExtension::Js
+  }
+}
+
+impl SpecifierExtension {
+  /// Gets the path to use in specifiers for the provided output file path.
+  pub(crate) fn get_specifier_path(&self, file_path: &Path) -> PathBuf {
+    match self {
+      SpecifierExtension::None => strip_extension(file_path),
+      SpecifierExtension::Js => {
+        let extension = match get_extension(file_path).as_deref() {
+          Some("mts") | Some("mjs") | Some("d.mts") => "mjs",
+          Some("cts") | Some("cjs") | Some("d.cts") => "cjs",
+          _ => "js",
+        };
+        with_extension(file_path, extension)
+      }
+      SpecifierExtension::Original => file_path.to_path_buf(),
+      SpecifierExtension::Mjs => with_extension(file_path, "mjs"),
+      SpecifierExtension::Cjs => with_extension(file_path, "cjs"),
+    }
+  }
+
+  /// Gets the extension to use for CommonJS output.
+  pub(crate) fn for_cjs(self) -> Self {
+    match self {
+      SpecifierExtension::Mjs => SpecifierExtension::Cjs,
+      _ => self,
+    }
+  }
+
+  /// Gets the path to output the provided file at so that its extension
+  /// matches the specifiers (ex. `mod.ts` -> `mod.mts` for `.mjs`).
+  ///
+  /// Returns `None` when the file's extension can't be changed to match.
+  pub(crate) fn get_output_file_path(
+    &self,
+    file_path: &Path,
+  ) -> Option<PathBuf> {
+    let (ts_extension, js_extension) = match self {
+      SpecifierExtension::Mjs => ("mts", "mjs"),
+      SpecifierExtension::Cjs => ("cts", "cjs"),
+      _ => return Some(file_path.to_path_buf()),
+    };
+    let extension = match get_extension(file_path).as_deref() {
+      Some("d.ts") | Some("d.mts") | Some("d.cts") => {
+        format!("d.{}", ts_extension)
+      }
+      Some("ts") | Some("mts") | Some("cts") => ts_extension.to_string(),
+      Some("js") | Some("mjs") | Some("cjs") => js_extension.to_string(),
+      _ => return None,
+    };
+    Some(with_extension(file_path, &extension))
+  }
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeDirectiveMode {
+  /// Removes `@deno-types` comments and triple-slash reference directives.
+  Remove,
+  /// Rewrites the specifiers in `@deno-types` comments and triple-slash
+  /// reference directives to point at the output files. Directives
+  /// pointing at files that are not output are removed.
+  Rewrite,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestHarness {
+  /// Uses the `test` function from `node:test`.
+  NodeTest,
+  /// Uses a named export of the provided module, which will be called
+  /// with the same arguments as `Deno.test`.
+  Custom { module: String, export_name: String },
+}
+
+impl TestHarness {
+  pub fn module(&self) -> &str {
+    match self {
+      TestHarness::NodeTest => "node:test",
+      TestHarness::Custom { module, .. } => module,
+    }
+  }
+
+  pub fn export_name(&self) -> &str {
+    match self {
+      TestHarness::NodeTest => "test",
+      TestHarness::Custom { export_name, .. } => export_name,
+    }
+  }
+}
+
+pub struct TestOptions {
+  pub entry_points: Vec<ModuleSpecifier>,
+  pub harness: TestHarness,
+  /// Module to use instead of the Deno standard library's
+  /// `testing/asserts.ts` module. When provided, the standard library
+  /// module will not be downloaded.
+  pub assertion_module: Option<String>,
+}
+
+pub struct BundleOptions {
+  /// Keeps imports of bare specifiers (ex. the assertion module) in the
+  /// bundle. Otherwise, bundling errors when encountering one. The shim
+  /// package import is always kept.
+  pub keep_bare_externals: bool,
+}
+
+pub struct SourceHeaderOptions {
+  /// Also includes the SHA-256 hash of the original source text.
+  pub include_hash: bool,
+}
+
+pub struct ThirdPartyNoticesOptions {
+  /// Fetches the closest `LICENSE` file at or above the root url of
+  /// each set of remote modules, stopping at the versioned package
+  /// directory (ex. `https://deno.land/x/mod@1.0.0/LICENSE` for modules
+  /// in `https://deno.land/x/mod@1.0.0/src/`).
+  pub fetch_license_files: bool,
+}
+
+/// Specifiers to exclude from the output.
+#[cfg_attr(feature = "serialization", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct External {
+  /// Prefix of the resolved specifiers to match (ex.
+  /// `https://deno.land/x/sdk@1.0.0/` or `file:///project/generated.ts`).
+  pub prefix: String,
+  /// Text to replace the matched prefix with in module specifiers
+  /// (ex. `sdk/`). When not provided, the module specifiers are left as-is.
+  pub replacement: Option<String>,
+}
+
+impl External {
+  /// Gets if the specifier is matched by the prefix. Prefixes that don't
+  /// end with a `/` or `@` only match up to a path boundary, so
+  /// `https://deno.land/x/sdk` doesn't match `https://deno.land/x/sdk2/`.
+  pub(crate) fn matches(&self, specifier: &str) -> bool {
+    match specifier.strip_prefix(&self.prefix) {
+      Some(rest) => {
+        rest.is_empty()
+          || rest.starts_with('/')
+          || self.prefix.ends_with('/')
+          || self.prefix.ends_with('@')
+      }
+      None => false,
+    }
+  }
+
+  /// Gets the new text for the module specifier or `None` when it
+  /// should be left as-is.
+  pub(crate) fn get_replacement(&self, specifier: &str) -> Option<String> {
+    self.replacement.as_ref().map(|replacement| {
+      format!("{}{}", replacement, &specifier[self.prefix.len()..])
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Shim {
+  /// Imports the `Deno` namespace from the provided package.
+  Package(String),
+  /// Outputs a `_dnt.shims.ts` module implementing the used `Deno`
+  /// members with Node.js APIs, which modules import via relative paths.
+  Local,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShimImportStyle {
+  /// `import * as denoShim from "..."` with `denoShim.Deno` references.
+  Namespace,
+  /// `import { Deno as denoShimDeno } from "..."`, which bundlers are
+  /// able to tree shake.
+  Named,
 }
 
 pub struct TransformOptions {
   pub entry_point: ModuleSpecifier,
-  pub keep_extensions: bool,
-  pub shim_package_name: Option<String>,
+  pub specifier_extension: SpecifierExtension,
+  pub module_format: ModuleFormat,
+  pub type_directives: TypeDirectiveMode,
+  pub shim: Shim,
+  pub shim_import_style: ShimImportStyle,
   pub loader: Option<Box<dyn Loader>>,
+  /// Transforms the provided test modules and the modules only they
+  /// depend on, rewriting `Deno.test` calls to use a test harness.
+  pub test: Option<TestOptions>,
+  /// Outputs the main modules as a single JavaScript module.
+  pub bundle: Option<BundleOptions>,
+  /// Declaration file to use for a module when multiple modules reference
+  /// different declaration files for it (ex. via `@deno-types`).
+  pub types_overrides: HashMap<ModuleSpecifier, ModuleSpecifier>,
+  /// Replacements for `Deno` member expression paths, which are used
+  /// instead of the shim (ex. `Deno.build.os` to `process.platform`).
+  /// Replacements that aren't simple expressions are parenthesized.
+  pub defines: HashMap<String, String>,
+  /// `Deno` member paths supported by the shim (ex. `Deno.readTextFile`
+  /// or `Deno.build`). When provided, transforming errors on any shimmed
+  /// usage that isn't one of these or a member of one of these.
+  pub supported_deno_apis: Option<Vec<String>>,
+  /// Modules that should not be loaded or output.
+  pub externals: Vec<External>,
+  /// Custom visitors run on every transformed module.
+  pub visitors: Vec<Box<dyn TextChangeVisitor>>,
+  /// Collects the licenses of the remote modules and outputs them
+  /// to a `THIRD_PARTY_NOTICES` file.
+  pub third_party_notices: Option<ThirdPartyNoticesOptions>,
+  /// Prepends a `// Source: <url>` comment to every remote module.
+  pub source_headers: Option<SourceHeaderOptions>,
 }
 
-pub async fn transform(options: TransformOptions) -> Result<Vec<OutputFile>> {
-  let shim_package_name = options
-    .shim_package_name
-    .unwrap_or_else(|| "shim-package-name".to_string());
-  let mut loader =
-    loader::SourceLoader::new(options.loader.unwrap_or_else(|| {
+pub async fn transform(options: TransformOptions) -> Result<TransformOutput> {
+  let test_options = options.test.as_ref();
+  if options.bundle.is_some() {
+    if options.module_format == ModuleFormat::Dual {
+      anyhow::bail!("Bundling is not supported when outputting CommonJS.");
+    }
+    if test_options.is_some() {
+      anyhow::bail!("Bundling is not supported with test entry points.");
+    }
+  }
+  let assertion_module = test_options
+    .and_then(|test_options| test_options.assertion_module.as_deref());
+  let mut loader = loader::SourceLoader::new(
+    options.loader.unwrap_or_else(|| {
       #[cfg(feature = "tokio-loader")]
       return Box::new(loader::DefaultLoader::new());
       #[cfg(not(feature = "tokio-loader"))]
       panic!("You must provide a loader or use the 'tokio-loader' feature.")
-    }));
+    }),
+    assertion_module.is_some(),
+    options.externals.clone(),
+  );
   let source_parser = parser::CapturingSourceParser::new();
-  let module_graph = create_graph(
-    options.entry_point.clone(),
+  let mut module_graph = ModuleGraph::default();
+  let mut jsdoc_scanned_specifiers = HashSet::new();
+  module_graph
+    .add_root(options.entry_point.clone(), &mut loader, &source_parser)
+    .await;
+  add_jsdoc_import_roots(
+    &mut module_graph,
+    &mut loader,
+    &source_parser,
+    &mut jsdoc_scanned_specifiers,
+  )
+  .await?;
+  let main_specifiers = loader.take_specifiers();
+  for entry_point in test_options.iter().flat_map(|o| o.entry_points.iter()) {
+    module_graph
+      .add_root(entry_point.clone(), &mut loader, &source_parser)
+      .await;
+  }
+  add_jsdoc_import_roots(
+    &mut module_graph,
     &mut loader,
-    None,
-    None,
-    Some(&source_parser),
+    &source_parser,
+    &mut jsdoc_scanned_specifiers,
   )
-  .await;
+  .await?;
+  let test_specifiers = loader.take_specifiers();
 
-  let specifiers = get_specifiers_from_loader(loader, &module_graph)?;
+  let specifiers = get_specifiers_from_loader(
+    main_specifiers,
+    test_specifiers,
+    &module_graph,
+    &options.types_overrides,
+  )?;
 
   let mappings = Mappings::new(&module_graph, &specifiers)?;
 
+  let specifier_extension = options.specifier_extension;
+  let type_directives = options.type_directives;
+  let visitors = &options.visitors;
+  let externals = &options.externals;
+  let defines = &options.defines;
+  let shim = &options.shim;
+  let source_headers = options.source_headers.as_ref();
+  let shim_import_style = options.shim_import_style;
+  let local_shim_file_path = Path::new(shims::LOCAL_SHIM_FILE_NAME);
+  let transform_module = |specifier: &ModuleSpecifier,
+                          parsed_source: &ParsedSource,
+                          is_cjs: bool,
+                          is_test: bool|
+   -> Result<TransformedModule> {
+    let source_text = parsed_source.source().text();
+    let is_typescript = !matches!(
+      parsed_source.media_type(),
+      MediaType::JavaScript | MediaType::Jsx
+    );
+    let comments = parsed_source.comments().get_vec();
+    let ignore_directives = IgnoreDirectives::new(&comments, &source_text);
+    let jsdoc_imports = if is_typescript {
+      Vec::new()
+    } else {
+      get_jsdoc_imports(&comments)
+    };
+    let specifier_extension = if is_cjs {
+      specifier_extension.for_cjs()
+    } else {
+      specifier_extension
+    };
+    let shim_specifier = match shim {
+      Shim::Package(package_name) => package_name.clone(),
+      Shim::Local => get_relative_specifier(
+        mappings.get_file_path(specifier),
+        specifier_extension.get_specifier_path(local_shim_file_path),
+      ),
+    };
+    let (text_changes, dependencies, deno_global_text_changes) = parsed_source
+      .with_view(|program| -> Result<_> {
+        let module_specifier_text_changes = get_module_specifier_text_changes(
+          &GetModuleSpecifierTextChangesParams {
+            specifier,
+            module_graph: &module_graph,
+            mappings: &mappings,
+            specifier_extension,
+            program: &program,
+            jsdoc_imports: &jsdoc_imports,
+            assertion_module,
+            externals,
+            ignore_directives: &ignore_directives,
+          },
+        )?;
+        let cjs_text_changes =
+          if is_cjs && parsed_source.media_type() != MediaType::Dts {
+            get_cjs_text_changes(&GetCjsTextChangesParams {
+              specifier,
+              program: &program,
+              source_text: &source_text,
+              is_typescript,
+              module_specifier_text_changes: &module_specifier_text_changes
+                .text_changes,
+              ignore_directives: &ignore_directives,
+            })?
+          } else {
+            Vec::new()
+          };
+        let mut text_changes = Vec::new();
+        if let Some(source_headers) = source_headers {
+          if matches!(specifier.scheme(), "http" | "https") {
+            text_changes.push(get_source_header_text_change(
+              specifier,
+              &source_text,
+              source_headers.include_hash,
+            ));
+          }
+        }
+        text_changes.extend(module_specifier_text_changes.text_changes);
+        text_changes.extend(get_type_directive_text_changes(
+          &GetTypeDirectiveTextChangesParams {
+            specifier,
+            mappings: &mappings,
+            specifier_extension,
+            comments: &comments,
+            source_text: &source_text,
+            mode: type_directives,
+            ignore_directives: &ignore_directives,
+          },
+        ));
+        text_changes.extend(cjs_text_changes);
+
+        let mut custom_text_changes = Vec::new();
+        for visitor in visitors.iter() {
+          custom_text_changes.extend(visitor.visit(
+            &TextChangeVisitorParams {
+              specifier,
+              program: &program,
+              mappings: &mappings,
+            },
+          )?);
+        }
+        let mut deno_global_text_changes =
+          get_deno_global_text_changes(&GetDenoGlobalTextChangesParams {
+            program: &program,
+            top_level_context: parsed_source.top_level_context(),
+            shim_specifier: &shim_specifier,
+            shim_import_style,
+            ignore_directives: &ignore_directives,
+            custom_text_changes: &custom_text_changes,
+            defines,
+            is_cjs,
+            is_typescript,
+            test_harness: if is_test {
+              test_options.map(|test_options| &test_options.harness)
+            } else {
+              None
+            },
+          });
+        text_changes.extend(deno_global_text_changes.text_changes.drain(..));
+        text_changes.extend(custom_text_changes);
+        Ok((
+          text_changes,
+          module_specifier_text_changes.dependencies,
+          deno_global_text_changes,
+        ))
+      })?;
+
+    let deno_members = deno_global_text_changes
+      .shimmed_members
+      .into_iter()
+      .map(|(path, pos)| {
+        let (line, column) = get_line_and_column_display(&source_text, pos);
+        DenoMemberUsage { path, line, column }
+      })
+      .collect();
+    Ok(TransformedModule {
+      file_text: apply_text_changes(&source_text, text_changes)
+        .with_context(|| format!("Error transforming {}", specifier))?,
+      dependencies,
+      deno_members,
+      shimmed_types: deno_global_text_changes.shimmed_types,
+      uses_shim: deno_global_text_changes.uses_shim,
+    })
+  };
+
   // todo: parallelize
-  let mut result = Vec::new();
+  let mut main = TransformOutputEnvironment::default();
+  let mut test = TransformOutputEnvironment::default();
+  let mut deno_api_usage = Vec::new();
+  let mut output_modules = Vec::new();
+  let mut uses_shim = false;
+  let mut shimmed_types = BTreeSet::new();
   for specifier in specifiers
     .local
     .iter()
@@ -80,72 +708,377 @@ pub async fn transform(options: TransformOptions) -> Result<Vec<OutputFile>> {
     .chain(specifiers.types.iter().map(|(_, from)| from))
   {
     let parsed_source = source_parser.get_parsed_source(specifier)?;
+    let file_path = get_output_file_path(
+      mappings.get_file_path(specifier),
+      specifier_extension,
+    )?;
+    let media_type = parsed_source.media_type();
+    let kind = get_output_file_kind(specifier, media_type);
+    let is_test = specifiers.test_modules.contains(specifier);
+    let environment = if is_test { &mut test } else { &mut main };
 
-    let keep_extensions = options.keep_extensions;
-    let text_changes = parsed_source.with_view(|program| {
-      let mut text_changes = get_module_specifier_text_changes(
-        &GetModuleSpecifierTextChangesParams {
-          specifier,
-          module_graph: &module_graph,
-          mappings: &mappings,
-          use_js_extension: keep_extensions,
-          program: &program,
-        },
+    let transformed_module =
+      transform_module(specifier, &parsed_source, false, is_test)?;
+    output_modules.push(OutputModule {
+      specifier: specifier.to_string(),
+      file_path: file_path.clone(),
+      dependencies: transformed_module.dependencies,
+    });
+    environment.files.push(OutputFile {
+      file_path: file_path.clone(),
+      file_text: transformed_module.file_text,
+      media_type,
+      kind,
+      original_specifier: Some(specifier.to_string()),
+    });
+    if options.module_format == ModuleFormat::Dual {
+      environment.cjs_files.push(OutputFile {
+        file_path: get_output_file_path(
+          mappings.get_file_path(specifier),
+          specifier_extension.for_cjs(),
+        )?,
+        file_text: transform_module(specifier, &parsed_source, true, is_test)?
+          .file_text,
+        media_type,
+        kind,
+        original_specifier: Some(specifier.to_string()),
+      });
+    }
+    uses_shim = uses_shim || transformed_module.uses_shim;
+    shimmed_types.extend(transformed_module.shimmed_types);
+    if !transformed_module.deno_members.is_empty() {
+      deno_api_usage.push(DenoApiUsage {
+        specifier: specifier.to_string(),
+        file_path,
+        members: transformed_module.deno_members,
+      });
+    }
+  }
+
+  if *shim == Shim::Local && uses_shim {
+    let shim_file_path =
+      get_output_file_path(local_shim_file_path, specifier_extension)?;
+    if let Some(specifier) = mappings
+      .iter()
+      .find(|(_, file_path)| {
+        specifier_extension.get_output_file_path(file_path)
+          == Some(shim_file_path.clone())
+      })
+      .map(|(specifier, _)| specifier)
+    {
+      anyhow::bail!(
+        "Could not output the local shim because {} is output at {}.",
+        specifier,
+        shim_file_path.display(),
       );
-      text_changes.extend(get_deno_global_text_changes(
-        &GetDenoGlobalTextChangesParams {
-          program: &program,
-          top_level_context: parsed_source.top_level_context(),
-          shim_package_name: shim_package_name.as_str(),
-        },
-      ));
-      text_changes
+    }
+    let shim_members = get_local_shim_members(&deno_api_usage);
+    main.files.push(OutputFile {
+      file_path: shim_file_path,
+      file_text: shims::get_local_shim_text(
+        shim_members.as_ref(),
+        &shimmed_types,
+        false,
+      ),
+      media_type: MediaType::TypeScript,
+      kind: OutputFileKind::Local,
+      original_specifier: None,
     });
+    if options.module_format == ModuleFormat::Dual {
+      main.cjs_files.push(OutputFile {
+        file_path: get_output_file_path(
+          local_shim_file_path,
+          specifier_extension.for_cjs(),
+        )?,
+        file_text: shims::get_local_shim_text(
+          shim_members.as_ref(),
+          &shimmed_types,
+          true,
+        ),
+        media_type: MediaType::TypeScript,
+        kind: OutputFileKind::Local,
+        original_specifier: None,
+      });
+    }
+  }
 
-    let final_file_text = apply_text_changes(
-      parsed_source.source().text().to_string(),
-      text_changes,
-    );
+  if let Some(supported_apis) = &options.supported_deno_apis {
+    ensure_supported_deno_apis(&deno_api_usage, supported_apis)?;
+  }
 
-    result.push(OutputFile {
-      file_path: mappings.get_file_path(specifier).to_owned(),
-      file_text: final_file_text,
+  if let Some(bundle_options) = &options.bundle {
+    ensure_no_dynamic_imports(&output_modules)?;
+    let bundle_file = bundler::bundle(bundler::BundleParams {
+      files: &main.files,
+      entry_point: &get_output_file_path(
+        mappings.get_file_path(&options.entry_point),
+        specifier_extension,
+      )?,
+      specifier_extension,
+      shim_package_name: match shim {
+        Shim::Package(package_name) => Some(package_name.as_str()),
+        Shim::Local => None,
+      },
+      keep_bare_externals: bundle_options.keep_bare_externals,
+    })?;
+    // declaration files aren't bundled, so keep them alongside the bundle
+    main
+      .files
+      .retain(|file| file.kind == OutputFileKind::Declaration);
+    main.files.insert(0, bundle_file);
+  }
+
+  let mut third_party_licenses = Vec::new();
+  if let Some(notices_options) = &options.third_party_notices {
+    third_party_licenses = licenses::get_remote_licenses(
+      &mappings,
+      &source_parser,
+      &loader,
+      notices_options.fetch_license_files,
+    )
+    .await?;
+    main.files.push(OutputFile {
+      file_path: PathBuf::from(licenses::THIRD_PARTY_NOTICES_FILE_NAME),
+      file_text: licenses::get_third_party_notices_text(&third_party_licenses),
+      media_type: MediaType::Unknown,
+      kind: OutputFileKind::Local,
+      original_specifier: None,
     });
   }
 
-  Ok(result)
+  Ok(TransformOutput {
+    main,
+    test,
+    deno_api_usage,
+    third_party_licenses,
+    graph: OutputGraph {
+      mappings: mappings
+        .iter()
+        .map(|(specifier, file_path)| {
+          Ok((
+            specifier.to_string(),
+            get_output_file_path(file_path, specifier_extension)?,
+          ))
+        })
+        .collect::<Result<_>>()?,
+      modules: output_modules,
+      types: specifiers
+        .types
+        .iter()
+        .map(|(specifier, types_specifier)| OutputTypesDependency {
+          specifier: specifier.to_string(),
+          types_specifier: types_specifier.to_string(),
+        })
+        .collect(),
+    },
+  })
+}
+
+/// Gets the path to output the file at so that its extension matches
+/// the provided specifier extension.
+fn get_output_file_path(
+  file_path: &Path,
+  specifier_extension: SpecifierExtension,
+) -> Result<PathBuf> {
+  specifier_extension
+    .get_output_file_path(file_path)
+    .ok_or_else(|| {
+      anyhow::anyhow!(
+        concat!(
+          "Could not output {} because only TypeScript, JavaScript and ",
+          "declaration files can use the {:?} specifier extension.",
+        ),
+        file_path.display(),
+        specifier_extension,
+      )
+    })
+}
+
+fn get_output_file_kind(
+  specifier: &ModuleSpecifier,
+  media_type: MediaType,
+) -> OutputFileKind {
+  if media_type == MediaType::Dts {
+    OutputFileKind::Declaration
+  } else if specifier.scheme() == "file" {
+    OutputFileKind::Local
+  } else {
+    OutputFileKind::Remote
+  }
+}
+
+struct TransformedModule {
+  file_text: String,
+  dependencies: Vec<OutputDependency>,
+  deno_members: Vec<DenoMemberUsage>,
+  shimmed_types: Vec<String>,
+  uses_shim: bool,
+}
+
+/// Gets the top level `Deno` members the local shim needs to implement
+/// or `None` when the entire `Deno` namespace is used.
+fn get_local_shim_members(
+  deno_api_usage: &[DenoApiUsage],
+) -> Option<BTreeSet<String>> {
+  let mut members = BTreeSet::new();
+  for member in deno_api_usage.iter().flat_map(|usage| usage.members.iter()) {
+    let mut parts = member.path.split('.').skip(1);
+    match parts.next() {
+      Some(name) => {
+        members.insert(name.to_string());
+      }
+      None => return None,
+    }
+  }
+  Some(members)
+}
+
+fn ensure_supported_deno_apis(
+  deno_api_usage: &[DenoApiUsage],
+  supported_apis: &[String],
+) -> Result<()> {
+  let mut message = String::new();
+  for usage in deno_api_usage {
+    for member in usage.members.iter() {
+      if !member.is_supported(supported_apis) {
+        message.push_str(&format!(
+          "\n  {} ({}:{}:{})",
+          member.path, usage.specifier, member.line, member.column
+        ));
+      }
+    }
+  }
+  if message.is_empty() {
+    Ok(())
+  } else {
+    anyhow::bail!("Found unsupported Deno APIs:{}", message)
+  }
+}
+
+/// Errors on statically analyzable dynamic imports, which the bundle
+/// would otherwise still import at runtime.
+fn ensure_no_dynamic_imports(modules: &[OutputModule]) -> Result<()> {
+  for module in modules {
+    for dependency in module.dependencies.iter() {
+      if dependency.kind == OutputDependencyKind::Dynamic {
+        anyhow::bail!(
+          "Dynamic imports are not supported when bundling (import(\"{}\") in {}).",
+          dependency.specifier,
+          module.specifier,
+        );
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Adds the modules referenced by JSDoc import types in JavaScript modules
+/// (ex. `/** @type {import("./mod.ts").Options} */`) as roots of the graph
+/// since they aren't analyzed as dependencies.
+async fn add_jsdoc_import_roots(
+  module_graph: &mut ModuleGraph,
+  loader: &mut loader::SourceLoader,
+  source_parser: &parser::CapturingSourceParser,
+  scanned_specifiers: &mut HashSet<ModuleSpecifier>,
+) -> Result<()> {
+  loop {
+    let mut roots = BTreeSet::new();
+    for specifier in source_parser.specifiers() {
+      if !scanned_specifiers.insert(specifier.clone()) {
+        continue;
+      }
+      let parsed_source = source_parser.get_parsed_source(&specifier)?;
+      if !matches!(
+        parsed_source.media_type(),
+        MediaType::JavaScript | MediaType::Jsx
+      ) {
+        continue;
+      }
+      let comments = parsed_source.comments().get_vec();
+      for jsdoc_import in get_jsdoc_imports(&comments) {
+        if let Ok(import_specifier) = specifier.join(&jsdoc_import.value) {
+          let import_specifier = module_graph.resolve(&import_specifier);
+          if module_graph.get(&import_specifier).is_none() {
+            roots.insert(import_specifier);
+          }
+        }
+      }
+    }
+
+    if roots.is_empty() {
+      return Ok(());
+    }
+    for root in roots {
+      module_graph.add_root(root, loader, source_parser).await;
+    }
+  }
 }
 
 fn get_specifiers_from_loader(
-  loader: SourceLoader,
+  main_specifiers: LoaderSpecifiers,
+  test_specifiers: LoaderSpecifiers,
   module_graph: &ModuleGraph,
+  types_overrides: &HashMap<ModuleSpecifier, ModuleSpecifier>,
 ) -> Result<Specifiers> {
-  let specifiers = loader.into_specifiers();
-  let mut types = BTreeMap::new();
+  let test_modules = test_specifiers
+    .local
+    .iter()
+    .chain(test_specifiers.remote.iter())
+    .cloned()
+    .collect::<HashSet<_>>();
+  let local = main_specifiers
+    .local
+    .into_iter()
+    .chain(test_specifiers.local.into_iter())
+    .collect::<Vec<_>>();
+  let remote = main_specifiers
+    .remote
+    .into_iter()
+    .chain(test_specifiers.remote.into_iter())
+    .collect::<Vec<_>>();
+  let mut types_candidates = BTreeMap::new();
 
-  handle_specifiers(&specifiers.local, module_graph, &mut types)?;
-  handle_specifiers(&specifiers.remote, module_graph, &mut types)?;
+  handle_specifiers(&local, module_graph, &mut types_candidates)?;
+  handle_specifiers(&remote, module_graph, &mut types_candidates)?;
 
-  let type_specifiers = types.values().collect::<HashSet<_>>();
+  // exclude every referenced declaration file from the regular output,
+  // including ones that lost out to another declaration file
+  let type_specifiers = types_candidates
+    .values()
+    .flat_map(|candidates| candidates.iter().map(|c| &c.types_specifier))
+    .cloned()
+    .collect::<HashSet<_>>();
+  let mut types = BTreeMap::new();
+  for (specifier, candidates) in types_candidates.into_iter() {
+    let types_specifier =
+      resolve_types_candidates(&specifier, candidates, types_overrides)?;
+    types.insert(specifier, types_specifier);
+  }
 
   return Ok(Specifiers {
-    local: specifiers
-      .local
-      .into_iter()
-      .filter(|l| !type_specifiers.contains(&l))
+    local: local
+      .iter()
+      .filter(|l| !type_specifiers.contains(l))
+      .cloned()
       .collect(),
-    remote: specifiers
-      .remote
-      .into_iter()
-      .filter(|l| !type_specifiers.contains(&l))
+    remote: remote
+      .iter()
+      .filter(|l| !type_specifiers.contains(l))
+      .cloned()
       .collect(),
     types,
+    test_modules,
   });
 
+  struct TypesCandidate {
+    types_specifier: ModuleSpecifier,
+    span: deno_graph::Span,
+  }
+
   fn handle_specifiers(
     specifiers: &[ModuleSpecifier],
     module_graph: &ModuleGraph,
-    types: &mut BTreeMap<ModuleSpecifier, ModuleSpecifier>,
+    types: &mut BTreeMap<ModuleSpecifier, Vec<TypesCandidate>>,
   ) -> Result<()> {
     for specifier in specifiers {
       let module = module_graph.try_get(specifier).map_err(|err| {
@@ -161,13 +1094,105 @@ fn get_specifiers_from_loader(
           text,
           err.to_string()
         ),
-        Some((_, Resolved::Specifier(type_specifier, _))) => {
-          types.insert(specifier.clone(), type_specifier.clone());
+        Some((_, Resolved::Specifier(type_specifier, span))) => {
+          ensure_declaration_file(specifier, type_specifier, module_graph)?;
+          types
+            .entry(specifier.clone())
+            .or_default()
+            .push(TypesCandidate {
+              types_specifier: type_specifier.clone(),
+              span: span.clone(),
+            });
         }
         _ => {}
       }
+
+      // @deno-types directives
+      for dependency in module.dependencies.values().iter() {
+        if let (
+          Resolved::Specifier(code_specifier, _),
+          Resolved::Specifier(type_specifier, span),
+        ) = (&dependency.maybe_code, &dependency.maybe_type)
+        {
+          ensure_declaration_file(
+            code_specifier,
+            type_specifier,
+            module_graph,
+          )?;
+          types.entry(code_specifier.clone()).or_default().push(
+            TypesCandidate {
+              types_specifier: type_specifier.clone(),
+              span: span.clone(),
+            },
+          );
+        }
+      }
     }
 
     Ok(())
   }
+
+  fn resolve_types_candidates(
+    specifier: &ModuleSpecifier,
+    mut candidates: Vec<TypesCandidate>,
+    types_overrides: &HashMap<ModuleSpecifier, ModuleSpecifier>,
+  ) -> Result<ModuleSpecifier> {
+    let mut seen = HashSet::new();
+    candidates.retain(|c| seen.insert(c.types_specifier.clone()));
+    if candidates.len() == 1 {
+      return Ok(candidates.remove(0).types_specifier);
+    }
+
+    match types_overrides.get(specifier) {
+      Some(types_specifier) if seen.contains(types_specifier) => {
+        Ok(types_specifier.clone())
+      }
+      Some(types_specifier) => anyhow::bail!(
+        "The types override {} for {} is not referenced by any module.",
+        types_specifier,
+        specifier,
+      ),
+      None => {
+        let mut message =
+          format!("Found conflicting declaration files for {}:", specifier);
+        for candidate in candidates.iter() {
+          message.push_str(&format!(
+            "\n  {} (referenced at {}:{}:{})",
+            candidate.types_specifier,
+            candidate.span.specifier,
+            candidate.span.start.line + 1,
+            candidate.span.start.character + 1,
+          ));
+        }
+        message.push_str(
+          "\nSpecify which declaration file to use via the types overrides.",
+        );
+        anyhow::bail!("{}", message)
+      }
+    }
+  }
+
+  fn ensure_declaration_file(
+    specifier: &ModuleSpecifier,
+    type_specifier: &ModuleSpecifier,
+    module_graph: &ModuleGraph,
+  ) -> Result<()> {
+    // the types module will be output as a declaration file, so
+    // implementation code would produce an invalid .d.ts file
+    let media_type = module_graph
+      .get(type_specifier)
+      .map(|module| module.media_type);
+    match media_type {
+      Some(MediaType::Dts) | None => Ok(()),
+      Some(media_type) => anyhow::bail!(
+        concat!(
+          "Only .d.ts files are supported as the types of {}, ",
+          "but found a {} file for: {}",
+        ),
+        specifier,
+        media_type.as_ts_extension(),
+        type_specifier,
+      ),
+    }
+  }
 }
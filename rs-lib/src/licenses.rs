@@ -0,0 +1,166 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use anyhow::Result;
+use deno_ast::swc::common::comments::Comment;
+use deno_ast::swc::common::comments::CommentKind;
+use deno_ast::ModuleSpecifier;
+
+use crate::loader::SourceLoader;
+use crate::mappings::Mappings;
+use crate::parser::CapturingSourceParser;
+use crate::LicenseComment;
+use crate::RemoteLicense;
+
+/// File path of the third party notices relative to the output root.
+pub const THIRD_PARTY_NOTICES_FILE_NAME: &str = "THIRD_PARTY_NOTICES";
+
+/// Gets the license information of each remote root, optionally
+/// fetching the closest `LICENSE` file at or above the root url
+/// within its package.
+pub async fn get_remote_licenses(
+  mappings: &Mappings,
+  source_parser: &CapturingSourceParser,
+  loader: &SourceLoader,
+  fetch_license_files: bool,
+) -> Result<Vec<RemoteLicense>> {
+  let mut licenses = Vec::new();
+  for root in mappings.remote_roots() {
+    let mut comments = Vec::new();
+    for specifier in root.specifiers.iter() {
+      let parsed_source = source_parser.get_parsed_source(specifier)?;
+      let source_text = parsed_source.source().text();
+      if let Some(text) = get_leading_license_text(
+        parsed_source.comments().get_vec(),
+        &source_text,
+      ) {
+        comments.push(LicenseComment {
+          specifier: specifier.to_string(),
+          text,
+        });
+      }
+    }
+    let license_file_text = if fetch_license_files {
+      fetch_license_file_text(&root.specifier, loader).await
+    } else {
+      None
+    };
+    licenses.push(RemoteLicense {
+      root: root.specifier.to_string(),
+      dir_path: root.dir_path.clone(),
+      comments,
+      license_file_text,
+    });
+  }
+  Ok(licenses)
+}
+
+/// Fetches the closest `LICENSE` file starting at the provided directory
+/// and walking up to the root of its package, since the remote root is
+/// the common directory of the imported modules and not necessarily the
+/// root of the package (ex. `https://deno.land/x/mod@1.0.0/src/`).
+async fn fetch_license_file_text(
+  dir: &ModuleSpecifier,
+  loader: &SourceLoader,
+) -> Option<String> {
+  for dir in get_license_dirs(dir) {
+    if let Ok(license_specifier) = dir.join("LICENSE") {
+      // not every directory has a license file, so ignore failures
+      if let Ok(text) = loader.fetch_remote_text(license_specifier).await {
+        return Some(text);
+      }
+    }
+  }
+  None
+}
+
+/// Gets the directories to search for a `LICENSE` file from the provided
+/// directory up to the package root, which is the directory of the last
+/// versioned path segment (ex. `std@0.110.0/` or `x/pkg@v1/`). Only the
+/// provided directory is searched when there is no versioned segment so
+/// that a license further up the host (ex. `https://deno.land/LICENSE`)
+/// isn't mistaken for the package's license.
+fn get_license_dirs(dir: &ModuleSpecifier) -> Vec<ModuleSpecifier> {
+  let mut dirs = vec![dir.clone()];
+  // ex. `/x/pkg@v1/src/` -> ["", "x", "pkg@v1", "src", ""]
+  let segments = dir.path().split('/').collect::<Vec<_>>();
+  let maybe_package_index = segments
+    .iter()
+    .rposition(|segment| matches!(segment.find('@'), Some(index) if index > 0));
+  if let Some(package_index) = maybe_package_index {
+    let mut current = dir.clone();
+    for _ in package_index + 2..segments.len() {
+      current = match current.join("../") {
+        Ok(parent) => parent,
+        Err(_) => break,
+      };
+      dirs.push(current.clone());
+    }
+  }
+  dirs
+}
+
+/// Gets the text of the `THIRD_PARTY_NOTICES` file.
+pub fn get_third_party_notices_text(licenses: &[RemoteLicense]) -> String {
+  let separator = "-".repeat(72);
+  let mut text = String::from(concat!(
+    "THIRD PARTY NOTICES\n\n",
+    "This package includes code from the following third party sources.\n",
+  ));
+  for license in licenses {
+    text.push_str(&format!(
+      "\n{}\n{} ({})\n{}\n",
+      separator,
+      license.root,
+      license.dir_path.to_string_lossy().replace("\\", "/"),
+      separator,
+    ));
+    if let Some(license_file_text) = &license.license_file_text {
+      text.push_str(&format!("\n{}\n", license_file_text.trim_end()));
+    }
+    for comment in license.comments.iter() {
+      text.push_str(&format!("\n{}:\n\n{}\n", comment.specifier, comment.text));
+    }
+    if license.license_file_text.is_none() && license.comments.is_empty() {
+      text.push_str("\nNo license information found.\n");
+    }
+  }
+  text
+}
+
+/// Gets the text of the comments at the top of a module when they
+/// mention a license or copyright.
+fn get_leading_license_text(
+  mut comments: Vec<Comment>,
+  source_text: &str,
+) -> Option<String> {
+  comments.sort_by_key(|comment| comment.span.lo);
+  let mut lines = Vec::new();
+  let mut last_end = 0;
+  for comment in comments.iter() {
+    let lo = comment.span.lo.0 as usize;
+    if lo < last_end || !source_text[last_end..lo].trim().is_empty() {
+      break;
+    }
+    last_end = comment.span.hi.0 as usize;
+    match comment.kind {
+      CommentKind::Line => lines.push(comment.text.trim()),
+      CommentKind::Block => {
+        for line in comment.text.lines() {
+          let line = line.trim();
+          lines.push(line.strip_prefix('*').unwrap_or(line).trim());
+        }
+      }
+    }
+  }
+
+  let text = lines.join("\n").trim().to_string();
+  let lower_text = text.to_lowercase();
+  if lower_text.contains("license")
+    || lower_text.contains("licence")
+    || lower_text.contains("copyright")
+  {
+    Some(text)
+  } else {
+    None
+  }
+}
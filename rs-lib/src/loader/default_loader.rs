@@ -6,6 +6,7 @@ use std::pin::Pin;
 use anyhow::Result;
 use deno_ast::ModuleSpecifier;
 use futures::Future;
+use reqwest::StatusCode;
 
 use crate::LoadResponse;
 use crate::Loader;
@@ -32,6 +33,7 @@ impl Loader for DefaultLoader {
   ) -> Pin<Box<dyn Future<Output = Result<LoadResponse>> + 'static>> {
     Box::pin(async move {
       let response = reqwest::get(specifier.clone()).await?;
+      ensure_success_status(&specifier, response.status())?;
       let headers = response
         .headers()
         .into_iter()
@@ -49,3 +51,31 @@ impl Loader for DefaultLoader {
     })
   }
 }
+
+fn ensure_success_status(
+  specifier: &ModuleSpecifier,
+  status: StatusCode,
+) -> Result<()> {
+  if !status.is_success() {
+    anyhow::bail!("Error fetching {} ({}).", specifier, status);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ensure_success_status_unsuccessful() {
+    let specifier = ModuleSpecifier::parse("http://localhost/LICENSE").unwrap();
+    assert!(ensure_success_status(&specifier, StatusCode::OK).is_ok());
+    assert_eq!(
+      ensure_success_status(&specifier, StatusCode::NOT_FOUND)
+        .err()
+        .unwrap()
+        .to_string(),
+      "Error fetching http://localhost/LICENSE (404 Not Found).",
+    );
+  }
+}
@@ -1,6 +1,7 @@
 // Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -10,7 +11,9 @@ use deno_ast::ModuleSpecifier;
 use futures::future;
 use futures::Future;
 
+use crate::utils::is_std_asserts_specifier;
 use crate::utils::url_to_file_path;
+use crate::External;
 
 #[cfg(feature = "tokio-loader")]
 mod default_loader;
@@ -34,6 +37,7 @@ pub trait Loader {
   ) -> Pin<Box<dyn Future<Output = Result<LoadResponse>> + 'static>>;
 }
 
+#[derive(Default)]
 pub struct LoaderSpecifiers {
   pub local: Vec<ModuleSpecifier>,
   pub remote: Vec<ModuleSpecifier>,
@@ -42,21 +46,48 @@ pub struct LoaderSpecifiers {
 pub struct SourceLoader {
   loader: Arc<Box<dyn Loader>>,
   specifiers: LoaderSpecifiers,
+  seen_specifiers: HashSet<ModuleSpecifier>,
+  skip_std_asserts: bool,
+  externals: Vec<External>,
 }
 
 impl SourceLoader {
-  pub fn new(loader: Box<dyn Loader>) -> Self {
+  pub fn new(
+    loader: Box<dyn Loader>,
+    skip_std_asserts: bool,
+    externals: Vec<External>,
+  ) -> Self {
     Self {
       loader: Arc::new(loader),
-      specifiers: LoaderSpecifiers {
-        local: Vec::new(),
-        remote: Vec::new(),
-      },
+      specifiers: Default::default(),
+      seen_specifiers: HashSet::new(),
+      skip_std_asserts,
+      externals,
     }
   }
 
-  pub fn into_specifiers(self) -> LoaderSpecifiers {
-    self.specifiers
+  /// Takes the specifiers loaded since the last time this was called.
+  pub fn take_specifiers(&mut self) -> LoaderSpecifiers {
+    std::mem::take(&mut self.specifiers)
+  }
+
+  /// Fetches the text of a remote file that is not part of the module
+  /// graph (ex. a `LICENSE` file).
+  pub async fn fetch_remote_text(
+    &self,
+    specifier: ModuleSpecifier,
+  ) -> Result<String> {
+    Ok(self.loader.make_request(specifier).await?.content)
+  }
+
+  fn should_skip(&self, specifier: &ModuleSpecifier) -> bool {
+    if self.skip_std_asserts && is_std_asserts_specifier(specifier) {
+      return true;
+    }
+    self
+      .externals
+      .iter()
+      .any(|external| external.matches(specifier.as_str()))
   }
 }
 
@@ -67,9 +98,17 @@ impl deno_graph::source::Loader for SourceLoader {
     // todo: handle dynamic
     _is_dynamic: bool,
   ) -> deno_graph::source::LoadFuture {
+    if self.should_skip(specifier) {
+      return Box::pin(future::ready((specifier.clone(), Ok(None))));
+    }
+
+    // the same module may be loaded once per graph
+    let is_new = self.seen_specifiers.insert(specifier.clone());
     if specifier.scheme() == "https" || specifier.scheme() == "http" {
-      println!("Downloading {}...", specifier);
-      self.specifiers.remote.push(specifier.clone());
+      if is_new {
+        println!("Downloading {}...", specifier);
+        self.specifiers.remote.push(specifier.clone());
+      }
 
       let loader = self.loader.clone();
       let specifier = specifier.clone();
@@ -87,8 +126,10 @@ impl deno_graph::source::Loader for SourceLoader {
         )
       });
     } else if specifier.scheme() == "file" {
-      println!("Loading {}...", specifier);
-      self.specifiers.local.push(specifier.clone());
+      if is_new {
+        println!("Loading {}...", specifier);
+        self.specifiers.local.push(specifier.clone());
+      }
 
       let file_path = url_to_file_path(specifier).unwrap();
       let loader = self.loader.clone();
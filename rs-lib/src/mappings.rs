@@ -3,15 +3,19 @@
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::path::Path;
 use std::path::PathBuf;
 
 use anyhow::Result;
 use deno_ast::MediaType;
 use deno_ast::ModuleSpecifier;
-use deno_graph::ModuleGraph;
 use regex::Regex;
 
+use crate::graph::ModuleGraph;
+use crate::utils::get_extension;
+use crate::utils::strip_extension;
 use crate::utils::url_to_file_path;
+use crate::utils::with_extension;
 
 lazy_static! {
   static ref HAS_EXTENSION_RE: Regex = Regex::new(r"\.[A-Za-z0-9]*$").unwrap();
@@ -21,14 +25,26 @@ pub struct Specifiers {
   pub local: Vec<ModuleSpecifier>,
   pub remote: Vec<ModuleSpecifier>,
   pub types: BTreeMap<ModuleSpecifier, ModuleSpecifier>,
+  /// Specifiers only found when analyzing the test entry points.
+  pub test_modules: HashSet<ModuleSpecifier>,
 }
 
+/// A remote root url and the remote modules output to its directory.
+pub(crate) struct RemoteRoot {
+  pub specifier: ModuleSpecifier,
+  /// Output directory (ex. `deps/0`).
+  pub dir_path: PathBuf,
+  pub specifiers: Vec<ModuleSpecifier>,
+}
+
+/// Mappings from module specifiers to their output file paths.
 pub struct Mappings {
   inner: HashMap<ModuleSpecifier, PathBuf>,
+  remote_roots: Vec<RemoteRoot>,
 }
 
 impl Mappings {
-  pub fn new(
+  pub(crate) fn new(
     module_graph: &ModuleGraph,
     specifiers: &Specifiers,
   ) -> Result<Self> {
@@ -86,13 +102,19 @@ impl Mappings {
     }
 
     let mut mapped_filepaths_no_ext = HashSet::new();
+    let mut remote_roots = Vec::new();
     for (i, (root, specifiers)) in
       root_remote_specifiers.into_iter().enumerate()
     {
       let base_dir = PathBuf::from(format!("deps/{}/", i.to_string()));
+      remote_roots.push(RemoteRoot {
+        specifier: root.clone(),
+        dir_path: PathBuf::from(format!("deps/{}", i)),
+        specifiers: specifiers.iter().map(|(s, _)| s.clone()).collect(),
+      });
       for (specifier, media_type) in specifiers {
         let relative = make_url_relative(&root, &specifier)?;
-        let mut filepath_no_ext = base_dir.join(relative).with_extension("");
+        let mut filepath_no_ext = strip_extension(&base_dir.join(relative));
         let original_file_name = filepath_no_ext
           .file_name()
           .unwrap()
@@ -104,28 +126,60 @@ impl Mappings {
             .set_file_name(format!("{}_{}", original_file_name, count));
           count += 1;
         }
-        let file_path =
-          filepath_no_ext.with_extension(&media_type.as_ts_extension()[1..]);
+        let file_path = with_extension(
+          &filepath_no_ext,
+          &get_remote_extension(&specifier, media_type),
+        );
         mappings.insert(specifier, file_path);
       }
     }
 
     for (from, to) in specifiers.types.iter() {
-      let file_path = mappings.get(&from).unwrap_or_else(|| {
-        panic!("Already had from {} in map when mapping to {}.", from, to)
-      });
-      let new_file_path = file_path.with_extension("d.ts");
-      if let Some(past_path) = mappings.insert(to.clone(), new_file_path) {
-        panic!(
-          "Already had path {} in map when mapping from {} to {}",
-          past_path.display(),
+      let file_path = mappings.get(&from).ok_or_else(|| {
+        anyhow::anyhow!(
+          "Programming error. Could not find file path for {} when mapping its types to {}.",
           from,
           to
+        )
+      })?;
+      let new_file_path = with_extension(
+        file_path,
+        match get_extension(file_path).as_deref() {
+          Some("mts") | Some("mjs") => "d.mts",
+          Some("cts") | Some("cjs") => "d.cts",
+          _ => "d.ts",
+        },
+      );
+      if let Some(past_path) = mappings.insert(to.clone(), new_file_path) {
+        anyhow::bail!(
+          "Could not map the types of {} to {} because it was already mapped to {}.",
+          from,
+          to,
+          past_path.display(),
         );
       }
     }
 
-    Ok(Mappings { inner: mappings })
+    Ok(Mappings {
+      inner: mappings,
+      remote_roots,
+    })
+  }
+
+  /// Iterates over the module specifiers and their output file paths.
+  pub fn iter(&self) -> impl Iterator<Item = (&ModuleSpecifier, &PathBuf)> {
+    self.inner.iter()
+  }
+
+  pub(crate) fn remote_roots(&self) -> &[RemoteRoot] {
+    &self.remote_roots
+  }
+
+  pub fn try_get_file_path(
+    &self,
+    specifier: &ModuleSpecifier,
+  ) -> Option<&PathBuf> {
+    self.inner.get(specifier)
   }
 
   pub fn get_file_path(&self, specifier: &ModuleSpecifier) -> &PathBuf {
@@ -138,6 +192,25 @@ impl Mappings {
   }
 }
 
+/// Gets the extension of the output file for a remote module, keeping
+/// module format specific extensions (ex. `.mts`) found in the url.
+fn get_remote_extension(
+  specifier: &ModuleSpecifier,
+  media_type: MediaType,
+) -> String {
+  match get_extension(Path::new(specifier.path())) {
+    Some(ext)
+      if matches!(
+        ext.as_str(),
+        "mts" | "cts" | "mjs" | "cjs" | "d.mts" | "d.cts"
+      ) =>
+    {
+      ext
+    }
+    _ => media_type.as_ts_extension()[1..].to_string(),
+  }
+}
+
 fn make_url_relative(
   root: &ModuleSpecifier,
   url: &ModuleSpecifier,
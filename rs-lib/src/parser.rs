@@ -26,6 +26,11 @@ impl CapturingSourceParser {
     }
   }
 
+  /// Gets the specifiers of all the modules parsed so far.
+  pub fn specifiers(&self) -> Vec<ModuleSpecifier> {
+    self.modules.borrow().keys().cloned().collect()
+  }
+
   pub fn get_parsed_source(
     &self,
     specifier: &ModuleSpecifier,
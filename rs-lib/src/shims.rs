@@ -0,0 +1,207 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use std::collections::BTreeSet;
+
+/// File path of the local shim module relative to the output root.
+pub const LOCAL_SHIM_FILE_NAME: &str = "_dnt.shims.ts";
+
+/// Node.js implementations of `Deno` members as
+/// (member name, uses the "fs" module, implementation).
+const MEMBER_IMPLEMENTATIONS: &[(&str, bool, &str)] = &[
+  ("args", false, "process.argv.slice(2)"),
+  (
+    "build",
+    false,
+    concat!(
+      "{\n",
+      "    os: process.platform === \"win32\" ? \"windows\" : process.platform,\n",
+      "    arch: process.arch === \"x64\" ? \"x86_64\" : process.arch,\n",
+      "  }"
+    ),
+  ),
+  ("chdir", false, "(directory: string) => process.chdir(directory)"),
+  ("cwd", false, "() => process.cwd()"),
+  (
+    "env",
+    false,
+    concat!(
+      "{\n",
+      "    get: (key: string) => process.env[key],\n",
+      "    set: (key: string, value: string) => {\n",
+      "      process.env[key] = value;\n",
+      "    },\n",
+      "    delete: (key: string) => {\n",
+      "      delete process.env[key];\n",
+      "    },\n",
+      "    toObject: () => ({ ...process.env }),\n",
+      "  }"
+    ),
+  ),
+  ("execPath", false, "() => process.execPath"),
+  ("exit", false, "(code?: number) => process.exit(code)"),
+  (
+    "mkdir",
+    true,
+    concat!(
+      "async (path: string | URL, options?: { recursive?: boolean }) => {\n",
+      "    await fs.promises.mkdir(path, options);\n",
+      "  }"
+    ),
+  ),
+  ("pid", false, "process.pid"),
+  (
+    "readFile",
+    true,
+    "async (path: string | URL) => new Uint8Array(await fs.promises.readFile(path))",
+  ),
+  (
+    "readFileSync",
+    true,
+    "(path: string | URL) => new Uint8Array(fs.readFileSync(path))",
+  ),
+  (
+    "readTextFile",
+    true,
+    "(path: string | URL) => fs.promises.readFile(path, \"utf8\")",
+  ),
+  (
+    "readTextFileSync",
+    true,
+    "(path: string | URL) => fs.readFileSync(path, \"utf8\")",
+  ),
+  (
+    "remove",
+    true,
+    "(path: string | URL, options?: { recursive?: boolean }) => fs.promises.rm(path, options)",
+  ),
+  (
+    "writeFile",
+    true,
+    "(path: string | URL, data: Uint8Array) => fs.promises.writeFile(path, data)",
+  ),
+  (
+    "writeFileSync",
+    true,
+    "(path: string | URL, data: Uint8Array) => fs.writeFileSync(path, data)",
+  ),
+  (
+    "writeTextFile",
+    true,
+    "(path: string | URL, data: string) => fs.promises.writeFile(path, data)",
+  ),
+  (
+    "writeTextFileSync",
+    true,
+    "(path: string | URL, data: string) => fs.writeFileSync(path, data)",
+  ),
+];
+
+/// Gets the text of the local shim module, which exports a `Deno` object
+/// implementing the provided members (ex. `readTextFile`) using Node.js.
+///
+/// All the known members are implemented when `None` is provided.
+/// Unknown members throw when accessed. The provided type paths
+/// (ex. `Deno.Reader`) are exported as `any` from a `Deno` namespace.
+pub fn get_local_shim_text(
+  maybe_members: Option<&BTreeSet<String>>,
+  type_paths: &BTreeSet<String>,
+  is_cjs: bool,
+) -> String {
+  let members = match maybe_members {
+    Some(members) => members.iter().map(|m| m.as_str()).collect::<Vec<_>>(),
+    None => MEMBER_IMPLEMENTATIONS
+      .iter()
+      .map(|(name, _, _)| *name)
+      .collect(),
+  };
+
+  let mut uses_fs = false;
+  let mut body = String::new();
+  for member in members {
+    match MEMBER_IMPLEMENTATIONS
+      .iter()
+      .find(|(name, _, _)| *name == member)
+    {
+      Some((name, member_uses_fs, implementation)) => {
+        uses_fs = uses_fs || *member_uses_fs;
+        body.push_str(&format!("  {}: {},\n", name, implementation));
+      }
+      None => {
+        body.push_str(&format!(
+          concat!(
+            "  get {}(): any {{\n",
+            "    throw new Error(\"Deno.{} is not implemented by the local shim.\");\n",
+            "  }},\n",
+          ),
+          member, member,
+        ));
+      }
+    }
+  }
+
+  let mut text = String::new();
+  if uses_fs {
+    text.push_str(if is_cjs {
+      "const fs = require(\"fs\");\n\n"
+    } else {
+      "import * as fs from \"fs\";\n\n"
+    });
+  }
+  if is_cjs {
+    // the value isn't declared locally so it doesn't conflict
+    // with the exported types namespace
+    text.push_str(&format!("module.exports.Deno = {{\n{}}};\n", body));
+  } else {
+    text.push_str(&format!("export const Deno: any = {{\n{}}};\n", body));
+  }
+  let types = get_types_namespace(type_paths);
+  if !types.is_empty() {
+    text.push_str(&format!("export namespace Deno {{\n{}}}\n", types));
+  }
+  if is_cjs {
+    text.push_str("export {};\n");
+  }
+  text
+}
+
+/// Gets the declarations of the `Deno` namespace for the provided type
+/// paths, nesting namespaces (ex. `Deno.errors.NotFound`) as necessary.
+fn get_types_namespace(type_paths: &BTreeSet<String>) -> String {
+  fn write_types(
+    text: &mut String,
+    parts: &[Vec<&str>],
+    depth: usize,
+    indent: usize,
+  ) {
+    let indent_text = "  ".repeat(indent);
+    let mut i = 0;
+    while i < parts.len() {
+      let name = parts[i][depth];
+      let end = i + parts[i..].iter().take_while(|p| p[depth] == name).count();
+      let (types, nested): (Vec<_>, Vec<_>) = parts[i..end]
+        .iter()
+        .cloned()
+        .partition(|p| p.len() == depth + 1);
+      if !types.is_empty() {
+        text.push_str(&format!("{}export type {} = any;\n", indent_text, name));
+      }
+      if !nested.is_empty() {
+        text
+          .push_str(&format!("{}export namespace {} {{\n", indent_text, name));
+        write_types(text, &nested, depth + 1, indent + 1);
+        text.push_str(&format!("{}}}\n", indent_text));
+      }
+      i = end;
+    }
+  }
+
+  // the paths are sorted, so types in the same namespace are adjacent
+  let parts = type_paths
+    .iter()
+    .map(|path| path.split('.').skip(1).collect::<Vec<_>>())
+    .filter(|parts| !parts.is_empty())
+    .collect::<Vec<_>>();
+  let mut text = String::new();
+  write_types(&mut text, &parts, 0, 1);
+  text
+}
@@ -1,7 +1,6 @@
 // Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
 
-use std::cmp::Ordering;
-
+use anyhow::Result;
 use deno_ast::swc::common::Span;
 
 pub struct TextChange {
@@ -9,25 +8,139 @@ pub struct TextChange {
   pub new_text: String,
 }
 
+/// Applies the text changes to the source in a single pass.
+///
+/// Insertions at the same position are applied in the order they were
+/// provided and come before any change that replaces text at that position.
+/// Errors when two changes overlap.
 pub fn apply_text_changes(
-  mut source: String,
+  source: &str,
   mut changes: Vec<TextChange>,
-) -> String {
-  changes.sort_by(|a, b| match a.span.lo.0.cmp(&b.span.lo.0) {
-    // reverse order
-    Ordering::Greater => Ordering::Less,
-    Ordering::Less => Ordering::Greater,
-    Ordering::Equal => Ordering::Equal,
-  });
+) -> Result<String> {
+  // stable sort, so insertions at the same position keep their order
+  changes.sort_by_key(|change| (change.span.lo.0, change.span.hi.0));
 
+  let mut final_text =
+    String::with_capacity(source.len() + get_new_text_len(&changes));
+  let mut last_index = 0;
+  let mut last_span: Option<Span> = None;
   for change in changes {
-    source = format!(
-      "{}{}{}",
-      &source[..change.span.lo.0 as usize],
-      change.new_text,
-      &source[change.span.hi.0 as usize..],
+    let lo = change.span.lo.0 as usize;
+    let hi = change.span.hi.0 as usize;
+    if lo > hi {
+      anyhow::bail!("Text change {}-{} starts after it ends.", lo, hi);
+    }
+    if let Some(last_span) = last_span {
+      if lo < last_index {
+        anyhow::bail!(
+          "Text change {}-{} overlaps with text change {}-{}.",
+          lo,
+          hi,
+          last_span.lo.0,
+          last_span.hi.0,
+        );
+      }
+    }
+    if hi > source.len() {
+      anyhow::bail!(
+        "Text change {}-{} is outside the text (length {}).",
+        lo,
+        hi,
+        source.len()
+      );
+    }
+    final_text.push_str(&source[last_index..lo]);
+    final_text.push_str(&change.new_text);
+    last_index = hi;
+    last_span = Some(change.span);
+  }
+  final_text.push_str(&source[last_index..]);
+
+  Ok(final_text)
+}
+
+fn get_new_text_len(changes: &[TextChange]) -> usize {
+  changes.iter().map(|change| change.new_text.len()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use deno_ast::swc::common::BytePos;
+
+  use super::*;
+
+  fn change(lo: u32, hi: u32, new_text: &str) -> TextChange {
+    // not using `Span::new` because it swaps reversed positions
+    TextChange {
+      span: Span {
+        lo: BytePos(lo),
+        hi: BytePos(hi),
+        ctxt: Default::default(),
+      },
+      new_text: new_text.to_string(),
+    }
+  }
+
+  #[test]
+  fn applies_changes() {
+    let text = apply_text_changes(
+      "let a = b;",
+      vec![change(8, 9, "c"), change(4, 5, "d")],
+    )
+    .unwrap();
+    assert_eq!(text, "let d = c;");
+  }
+
+  #[test]
+  fn errors_on_overlapping_replacements() {
+    let err = apply_text_changes(
+      "let a = b;",
+      vec![change(0, 5, ""), change(4, 9, "")],
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+      err.to_string(),
+      "Text change 4-9 overlaps with text change 0-5."
     );
   }
 
-  source
+  #[test]
+  fn allows_touching_changes() {
+    let text = apply_text_changes(
+      "let a = b;",
+      vec![change(4, 5, "c"), change(0, 4, "const "), change(5, 5, "d")],
+    )
+    .unwrap();
+    assert_eq!(text, "const cd = b;");
+  }
+
+  #[test]
+  fn keeps_order_of_insertions_at_same_position() {
+    let text = apply_text_changes(
+      "b;",
+      vec![change(0, 0, "1"), change(0, 1, "a"), change(0, 0, "2")],
+    )
+    .unwrap();
+    assert_eq!(text, "12a;");
+  }
+
+  #[test]
+  fn errors_on_reversed_span() {
+    let err = apply_text_changes("let a = b;", vec![change(20, 2, "")])
+      .err()
+      .unwrap();
+    assert_eq!(err.to_string(), "Text change 20-2 starts after it ends.");
+  }
+
+  #[test]
+  fn errors_on_span_outside_text() {
+    let err = apply_text_changes("let a = b;", vec![change(5, 20, "")])
+      .err()
+      .unwrap();
+    assert_eq!(
+      err.to_string(),
+      "Text change 5-20 is outside the text (length 10)."
+    );
+  }
 }
@@ -1,10 +1,26 @@
 // Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
 
+use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
 
 use anyhow::Result;
+use deno_ast::swc::common::BytePos;
+use deno_ast::swc::common::Span;
+use deno_ast::view::Node;
+use deno_ast::view::Program;
 use deno_ast::ModuleSpecifier;
+use regex::Regex;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::text_changes::TextChange;
+
+lazy_static! {
+  static ref STD_ASSERTS_RE: Regex =
+    Regex::new(r"^https://deno\.land/std(@[^/]+)?/testing/asserts\.ts$")
+      .unwrap();
+}
 
 pub fn get_relative_path(
   from: impl AsRef<Path>,
@@ -13,6 +29,94 @@ pub fn get_relative_path(
   pathdiff::diff_paths(to, from.as_ref().parent().unwrap()).unwrap()
 }
 
+/// Gets the relative specifier text (ex. `./mod.ts`) for importing
+/// one output file from another.
+pub fn get_relative_specifier(
+  from: impl AsRef<Path>,
+  to: impl AsRef<Path>,
+) -> String {
+  let relative_path_str = get_relative_path(from, to)
+    .to_string_lossy()
+    .to_string()
+    .replace("\\", "/");
+  if relative_path_str.starts_with("../") || relative_path_str.starts_with("./")
+  {
+    relative_path_str
+  } else {
+    format!("./{}", relative_path_str)
+  }
+}
+
+/// Declaration file extensions, which are treated as a single extension.
+const DECLARATION_EXTENSIONS: &[&str] = &["d.ts", "d.mts", "d.cts"];
+
+/// Gets the lowercase extension of the file path without the leading
+/// period (ex. `ts` or `d.ts`).
+pub fn get_extension(file_path: &Path) -> Option<String> {
+  let file_name = file_path.file_name()?.to_string_lossy().to_lowercase();
+  DECLARATION_EXTENSIONS
+    .iter()
+    .find(|ext| file_name.ends_with(&format!(".{}", ext)))
+    .map(|ext| ext.to_string())
+    .or_else(|| {
+      file_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+    })
+}
+
+/// Gets the file path without its extension (ex. `mod.d.ts` -> `mod`).
+pub fn strip_extension(file_path: &Path) -> PathBuf {
+  match get_extension(file_path) {
+    Some(ext) => {
+      let text = file_path.to_string_lossy();
+      PathBuf::from(&text[..text.len() - ext.len() - 1])
+    }
+    // normalizes any trailing slash
+    None => file_path.with_extension(""),
+  }
+}
+
+/// Gets the file path with its extension replaced. Unlike
+/// `Path::with_extension`, this handles declaration file extensions
+/// and file names containing periods.
+pub fn with_extension(file_path: &Path, extension: &str) -> PathBuf {
+  let mut path = strip_extension(file_path).into_os_string();
+  path.push(".");
+  path.push(extension);
+  PathBuf::from(path)
+}
+
+/// Gets the text change inserting the header comment stating where a
+/// remote module came from. The header goes after any leading `#!` line.
+pub fn get_source_header_text_change(
+  specifier: &ModuleSpecifier,
+  source_text: &str,
+  include_hash: bool,
+) -> TextChange {
+  let mut text = String::new();
+  let mut pos = 0;
+  if source_text.starts_with("#!") {
+    pos = match source_text.find('\n') {
+      Some(index) => index + 1,
+      None => {
+        text.push('\n');
+        source_text.len()
+      }
+    };
+  }
+  text.push_str(&format!("// Source: {}\n", specifier));
+  if include_hash {
+    let hash = Sha256::digest(source_text.as_bytes());
+    text.push_str(&format!("// SHA-256: {:x}\n", hash));
+  }
+  let pos = BytePos(pos as u32);
+  TextChange {
+    span: Span::new(pos, pos, Default::default()),
+    new_text: text,
+  }
+}
+
 pub fn url_to_file_path(module_specifier: &ModuleSpecifier) -> Result<PathBuf> {
   // module_specifier.to_file_path() does not work in a cross platform way
   // and it does not work in Wasm
@@ -34,6 +138,78 @@ pub fn url_to_file_path(module_specifier: &ModuleSpecifier) -> Result<PathBuf> {
   Ok(PathBuf::from(final_text))
 }
 
+pub fn is_std_asserts_specifier(specifier: &ModuleSpecifier) -> bool {
+  STD_ASSERTS_RE.is_match(specifier.as_str())
+}
+
+/// Gets the one-based line and column of a position in the text.
+pub fn get_line_and_column_display(text: &str, pos: BytePos) -> (usize, usize) {
+  let text_before = &text[..pos.0 as usize];
+  let line_start = text_before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+  let line = text_before.matches('\n').count() + 1;
+  let column = text_before[line_start..].chars().count() + 1;
+  (line, column)
+}
+
+/// Gets if the provided identifier is a type reference (ex. the `Deno`
+/// in `Deno.Reader` within a type annotation).
+pub fn is_type_position(node: &Node) -> bool {
+  let mut current = *node;
+  loop {
+    match current.parent() {
+      Some(Node::TsQualifiedName(qualified_name)) => {
+        current = Node::TsQualifiedName(qualified_name);
+      }
+      // ex. `import Reader = Deno.Reader;` references a value
+      Some(Node::TsImportEqualsDecl(_)) => return false,
+      Some(
+        Node::TsTypeRef(_) | Node::TsTypeQuery(_) | Node::TsExprWithTypeArgs(_),
+      ) => return true,
+      _ => return false,
+    }
+  }
+}
+
+/// Gets the names of the identifiers in the program, excluding member
+/// properties.
+pub fn get_all_ident_names(program: &Program) -> HashSet<String> {
+  let mut result = HashSet::new();
+  visit_children(&program.into(), &mut result);
+  return result;
+
+  fn visit_children(node: &Node, result: &mut HashSet<String>) {
+    for child in node.children() {
+      visit_children(&child, result);
+    }
+
+    if let Node::Ident(ident) = node {
+      if !is_member_prop(node) {
+        result.insert(ident.sym().to_string());
+      }
+    }
+  }
+
+  fn is_member_prop(node: &Node) -> bool {
+    match node.parent() {
+      Some(Node::MemberExpr(member_expr)) => {
+        !member_expr.computed() && member_expr.prop.span() == node.span()
+      }
+      _ => false,
+    }
+  }
+}
+
+/// Gets a name based on the provided name that isn't in `all_idents`.
+pub fn get_unique_name(name: &str, all_idents: &HashSet<String>) -> String {
+  let mut count = 0;
+  let mut new_name = name.to_string();
+  while all_idents.contains(&new_name) {
+    count += 1;
+    new_name = format!("{}{}", name, count);
+  }
+  new_name
+}
+
 fn is_windows_path_segment(specifier: &str) -> bool {
   let mut chars = specifier.chars();
 
@@ -0,0 +1,582 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use anyhow::Result;
+use deno_ast::swc::utils::find_ids;
+use deno_ast::swc::utils::ident::IdentLike;
+use deno_ast::swc::utils::Id;
+use deno_ast::view::*;
+use deno_ast::ModuleSpecifier;
+
+use crate::ignore_directives::IgnoreDirectives;
+use crate::text_changes::TextChange;
+use crate::utils::get_all_ident_names;
+use crate::utils::get_line_and_column_display;
+use crate::utils::get_unique_name;
+use crate::utils::is_type_position;
+
+pub struct GetCjsTextChangesParams<'a> {
+  pub specifier: &'a ModuleSpecifier,
+  pub program: &'a Program<'a>,
+  pub source_text: &'a str,
+  /// Whether the module is a TypeScript module. TypeScript needs an
+  /// `export {}` in order to keep treating the file as a module.
+  pub is_typescript: bool,
+  /// Text changes of the module specifier visitor, which are used when
+  /// a module specifier needs to be output more than once.
+  pub module_specifier_text_changes: &'a [TextChange],
+  pub ignore_directives: &'a IgnoreDirectives,
+}
+
+/// Gets the text changes to convert the ES module declarations of a module
+/// to `require` calls and `exports` properties.
+///
+/// Exports are defined as getters at the top of the module and imported
+/// values are accessed through the object returned by `require`, so that
+/// they stay live like ES module bindings (ex. when an exported variable is
+/// reassigned or when modules import each other in a cycle).
+///
+/// This only changes the text surrounding module specifiers so that it may
+/// be combined with the text changes from the module specifier visitor.
+/// Statements ignored by `dnt-ignore` directives are left as-is.
+pub fn get_cjs_text_changes<'a>(
+  params: &GetCjsTextChangesParams<'a>,
+) -> Result<Vec<TextChange>> {
+  if params.ignore_directives.is_file_ignored() {
+    return Ok(Vec::new());
+  }
+
+  ensure_no_top_level_await(params)?;
+
+  let referenced_ids = get_referenced_ids(params.program);
+  let mut used_names = get_all_ident_names(params.program);
+  // text to use instead of each imported value (ex. `_other.a`)
+  let mut imported_values = HashMap::new();
+  let mut text_changes = Vec::new();
+  let mut has_exports = false;
+  // exported name, local name, and the local identifier when it may be
+  // an imported value
+  let mut exports: Vec<(String, String, Option<Id>)> = Vec::new();
+  let mut maybe_start_pos = None;
+
+  for child in params.program.children() {
+    if params.ignore_directives.is_ignored(child.span().lo) {
+      continue;
+    }
+    if maybe_start_pos.is_none() {
+      maybe_start_pos = Some(child.span().lo);
+    }
+    match child {
+      Node::ImportDecl(import_decl) => {
+        if import_decl.type_only() {
+          continue;
+        }
+        let (prefix, suffix) = get_import_decl_text(
+          import_decl,
+          &referenced_ids,
+          &mut used_names,
+          &mut imported_values,
+          params,
+        );
+        push_surrounding_text_changes(
+          &mut text_changes,
+          import_decl.span(),
+          import_decl.src.span(),
+          prefix,
+          suffix,
+        );
+      }
+      Node::ExportAll(export_all) => {
+        has_exports = true;
+        push_surrounding_text_changes(
+          &mut text_changes,
+          export_all.span(),
+          export_all.src.span(),
+          "{ const m = require(".to_string(),
+          concat!(
+            "); for (const k in m) { ",
+            "if (k !== \"default\" && !(k in exports)) ",
+            "Object.defineProperty(exports, k, ",
+            "{ enumerable: true, get: () => m[k] }); } }"
+          )
+          .to_string(),
+        );
+      }
+      Node::NamedExport(named_export) => {
+        if named_export.type_only() {
+          continue;
+        }
+        has_exports = true;
+        match named_export.src {
+          Some(src) => {
+            let (prefix, suffix) = get_re_export_text(named_export);
+            push_surrounding_text_changes(
+              &mut text_changes,
+              named_export.span(),
+              src.span(),
+              prefix,
+              suffix,
+            );
+          }
+          None => {
+            for specifier in named_export.specifiers.iter() {
+              if let ExportSpecifier::Named(named) = specifier {
+                let local_name = named.orig.sym().to_string();
+                let exported_name = named
+                  .exported
+                  .map(|e| e.sym().to_string())
+                  .unwrap_or_else(|| local_name.clone());
+                exports.push((
+                  exported_name,
+                  local_name,
+                  Some(named.orig.inner.to_id()),
+                ));
+              }
+            }
+            text_changes.push(TextChange {
+              span: named_export.span(),
+              new_text: String::new(),
+            });
+          }
+        }
+      }
+      Node::ExportDecl(export_decl) => {
+        let names = get_exported_decl_names(&export_decl.decl);
+        if !names.is_empty() {
+          has_exports = true;
+          text_changes.push(TextChange {
+            span: Span::new(
+              export_decl.span().lo,
+              export_decl.decl.span().lo,
+              Default::default(),
+            ),
+            new_text: String::new(),
+          });
+          exports
+            .extend(names.into_iter().map(|name| (name.clone(), name, None)));
+        }
+      }
+      Node::ExportDefaultExpr(export_default_expr) => {
+        has_exports = true;
+        text_changes.push(TextChange {
+          span: Span::new(
+            export_default_expr.span().lo,
+            export_default_expr.expr.span().lo,
+            Default::default(),
+          ),
+          new_text: "exports.default = ".to_string(),
+        });
+      }
+      Node::ExportDefaultDecl(export_default_decl) => {
+        let (decl_span, maybe_ident) = match &export_default_decl.decl {
+          DefaultDecl::Class(class_expr) => {
+            (class_expr.span(), class_expr.ident)
+          }
+          DefaultDecl::Fn(fn_expr) => (fn_expr.span(), fn_expr.ident),
+          DefaultDecl::TsInterfaceDecl(_) => continue,
+        };
+        has_exports = true;
+        let prefix_span = Span::new(
+          export_default_decl.span().lo,
+          decl_span.lo,
+          Default::default(),
+        );
+        match maybe_ident {
+          Some(ident) => {
+            text_changes.push(TextChange {
+              span: prefix_span,
+              new_text: String::new(),
+            });
+            exports.push((
+              "default".to_string(),
+              ident.sym().to_string(),
+              None,
+            ));
+          }
+          None => {
+            text_changes.push(TextChange {
+              span: prefix_span,
+              new_text: "exports.default = ".to_string(),
+            });
+            text_changes.push(TextChange {
+              span: Span::new(decl_span.hi, decl_span.hi, Default::default()),
+              new_text: ";".to_string(),
+            });
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+
+  if !imported_values.is_empty() {
+    push_imported_value_text_changes(
+      &mut text_changes,
+      &params.program.into(),
+      &imported_values,
+    );
+  }
+
+  // define the exports before anything is required so that modules
+  // importing this one in a cycle are able to access them
+  let mut start_text = String::new();
+  if has_exports {
+    start_text.push_str(
+      "Object.defineProperty(exports, \"__esModule\", { value: true });\n",
+    );
+  }
+  for (exported_name, local_name, maybe_id) in exports {
+    let value = maybe_id
+      .and_then(|id| imported_values.get(&id).cloned())
+      .unwrap_or(local_name);
+    start_text.push_str(&format!(
+      concat!(
+        "Object.defineProperty(exports, \"{}\", ",
+        "{{ enumerable: true, get: () => {} }});\n",
+      ),
+      exported_name, value,
+    ));
+  }
+  if let Some(start_pos) = maybe_start_pos {
+    // after any leading comments (ex. triple-slash directives)
+    if !start_text.is_empty() {
+      text_changes.push(TextChange {
+        span: Span::new(start_pos, start_pos, Default::default()),
+        new_text: start_text,
+      });
+    }
+  }
+
+  if params.is_typescript {
+    let end_pos = params.program.span().hi;
+    text_changes.push(TextChange {
+      span: Span::new(end_pos, end_pos, Default::default()),
+      new_text: "\nexport {};".to_string(),
+    });
+  }
+
+  Ok(text_changes)
+}
+
+fn push_surrounding_text_changes(
+  text_changes: &mut Vec<TextChange>,
+  decl_span: Span,
+  src_span: Span,
+  prefix: String,
+  suffix: String,
+) {
+  text_changes.push(TextChange {
+    span: Span::new(decl_span.lo, src_span.lo, Default::default()),
+    new_text: prefix,
+  });
+  text_changes.push(TextChange {
+    span: Span::new(src_span.hi, decl_span.hi, Default::default()),
+    new_text: suffix,
+  });
+}
+
+/// Changes the references to the imported values to access them through
+/// the object returned by `require`.
+fn push_imported_value_text_changes(
+  text_changes: &mut Vec<TextChange>,
+  node: &Node,
+  imported_values: &HashMap<Id, String>,
+) {
+  match node {
+    // the import declarations are replaced and the local exports are
+    // defined at the top of the module
+    Node::ImportDecl(_) => return,
+    Node::NamedExport(named_export) if named_export.src.is_none() => return,
+    Node::Ident(ident) if !is_type_position(node) => {
+      if let Some(value) = imported_values.get(&ident.inner.to_id()) {
+        let new_text = match node.parent() {
+          // ex. `{ a }` to `{ a: _other.a }`
+          Some(Node::ObjectLit(_)) => format!("{}: {}", ident.sym(), value),
+          _ => value.clone(),
+        };
+        text_changes.push(TextChange {
+          span: ident.span(),
+          new_text,
+        });
+      }
+    }
+    _ => {}
+  }
+  for child in node.children() {
+    push_imported_value_text_changes(text_changes, &child, imported_values);
+  }
+}
+
+fn get_import_decl_text(
+  import_decl: &ImportDecl,
+  referenced_ids: &ReferencedIds,
+  used_names: &mut HashSet<String>,
+  imported_values: &mut HashMap<Id, String>,
+  params: &GetCjsTextChangesParams,
+) -> (String, String) {
+  let mut maybe_namespace = None;
+  // local identifiers of the values along with the name of their export
+  let mut values = Vec::new();
+  // bindings referenced in type positions can't be accessed through the
+  // object returned by `require`, so they're imported via type-only imports
+  let mut type_imports = Vec::new();
+  let mut type_named = Vec::new();
+  for specifier in import_decl.specifiers.iter() {
+    match specifier {
+      ImportSpecifier::Default(default) => {
+        let id = default.local.inner.to_id();
+        if referenced_ids.type_ids.contains(&id) {
+          type_imports
+            .push(format!("import type {} from ", default.local.sym()));
+        }
+        if referenced_ids.value_ids.contains(&id) {
+          values.push((id, "default".to_string()));
+        }
+      }
+      ImportSpecifier::Namespace(namespace) => {
+        let id = namespace.local.inner.to_id();
+        let local = namespace.local.sym().to_string();
+        if referenced_ids.value_ids.contains(&id) {
+          maybe_namespace = Some(local);
+        } else if referenced_ids.type_ids.contains(&id) {
+          type_imports.push(format!("import type * as {} from ", local));
+        }
+      }
+      ImportSpecifier::Named(named_specifier) => {
+        let id = named_specifier.local.inner.to_id();
+        let local = named_specifier.local.sym().to_string();
+        let imported = named_specifier
+          .imported
+          .map(|imported| imported.sym().to_string())
+          .unwrap_or_else(|| local.clone());
+        if referenced_ids.type_ids.contains(&id) {
+          type_named.push(if imported == local {
+            local
+          } else {
+            format!("{} as {}", imported, local)
+          });
+        }
+        if referenced_ids.value_ids.contains(&id) {
+          values.push((id, imported));
+        }
+      }
+    }
+  }
+  if !type_named.is_empty() {
+    type_imports
+      .push(format!("import type {{ {} }} from ", type_named.join(", ")));
+  }
+
+  let maybe_require_text = if maybe_namespace.is_some() || !values.is_empty() {
+    let namespace = maybe_namespace.unwrap_or_else(|| {
+      let name = get_unique_name(
+        &get_namespace_name(import_decl.src.value()),
+        used_names,
+      );
+      used_names.insert(name.clone());
+      name
+    });
+    for (id, export_name) in values {
+      imported_values.insert(id, format!("{}.{}", namespace, export_name));
+    }
+    Some((format!("const {} = require(", namespace), ");".to_string()))
+  } else if type_imports.is_empty() {
+    Some(("require(".to_string(), ");".to_string()))
+  } else {
+    None
+  };
+
+  // the last type import uses the module specifier in the source
+  // when nothing is required
+  let (last_prefix, suffix) = match maybe_require_text {
+    Some(require_text) => require_text,
+    None => (type_imports.pop().unwrap(), ";".to_string()),
+  };
+  let mut prefix = String::new();
+  if !type_imports.is_empty() {
+    let src_text = get_output_str_text(import_decl.src, params);
+    for type_import in type_imports {
+      prefix.push_str(&format!("{}{}; ", type_import, src_text));
+    }
+  }
+  prefix.push_str(&last_prefix);
+  (prefix, suffix)
+}
+
+/// Gets the name for the object returned by `require` based on the last
+/// path segment of the module specifier (ex. `_other` for `./other.ts`).
+fn get_namespace_name(specifier: &str) -> String {
+  let file_name = specifier.rsplit(|c| c == '/' || c == ':').next().unwrap();
+  let stem = file_name.split('.').next().unwrap();
+  let mut name = "_".to_string();
+  for c in stem.chars() {
+    name.push(if c.is_ascii_alphanumeric() || c == '$' {
+      c
+    } else {
+      '_'
+    });
+  }
+  if name == "_" {
+    name.push_str("mod");
+  }
+  name
+}
+
+/// Gets the text of the string literal with the module specifier
+/// visitor's changes applied.
+fn get_output_str_text(str: &Str, params: &GetCjsTextChangesParams) -> String {
+  let span = str.span();
+  let text = &params.source_text[span.lo.0 as usize..span.hi.0 as usize];
+  let inner_span = Span::new(
+    span.lo + BytePos(1),
+    span.hi - BytePos(1),
+    Default::default(),
+  );
+  match params
+    .module_specifier_text_changes
+    .iter()
+    .find(|text_change| text_change.span == inner_span)
+  {
+    Some(text_change) => {
+      format!("{0}{1}{0}", &text[..1], text_change.new_text)
+    }
+    None => text.to_string(),
+  }
+}
+
+/// Identifiers of the module by the positions they're referenced in.
+struct ReferencedIds {
+  type_ids: HashSet<Id>,
+  value_ids: HashSet<Id>,
+}
+
+fn get_referenced_ids(program: &Program) -> ReferencedIds {
+  let mut referenced_ids = ReferencedIds {
+    type_ids: HashSet::new(),
+    value_ids: HashSet::new(),
+  };
+  visit_children(&program.into(), &mut referenced_ids);
+  return referenced_ids;
+
+  fn visit_children(node: &Node, referenced_ids: &mut ReferencedIds) {
+    match node {
+      // the import declarations only declare the identifiers
+      Node::ImportDecl(_) => return,
+      Node::Ident(ident) => {
+        if is_type_position(node) {
+          referenced_ids.type_ids.insert(ident.inner.to_id());
+        } else {
+          referenced_ids.value_ids.insert(ident.inner.to_id());
+        }
+      }
+      _ => {}
+    }
+    for child in node.children() {
+      visit_children(&child, referenced_ids);
+    }
+  }
+}
+
+fn get_re_export_text(named_export: &NamedExport) -> (String, String) {
+  let mut assignments = Vec::new();
+  for specifier in named_export.specifiers.iter() {
+    match specifier {
+      ExportSpecifier::Namespace(namespace) => {
+        assignments.push((namespace.name.sym().to_string(), "m".to_string()));
+      }
+      ExportSpecifier::Default(default) => {
+        assignments
+          .push((default.exported.sym().to_string(), "m.default".to_string()));
+      }
+      ExportSpecifier::Named(named) => {
+        let orig_name = named.orig.sym().to_string();
+        let exported_name = named
+          .exported
+          .map(|e| e.sym().to_string())
+          .unwrap_or_else(|| orig_name.clone());
+        assignments.push((exported_name, format!("m.{}", orig_name)));
+      }
+    }
+  }
+
+  let mut suffix = ");".to_string();
+  for (exported_name, value) in assignments {
+    suffix.push_str(&format!(
+      concat!(
+        " Object.defineProperty(exports, \"{}\", ",
+        "{{ enumerable: true, get: () => {} }});",
+      ),
+      exported_name, value,
+    ));
+  }
+  suffix.push_str(" }");
+  ("{ const m = require(".to_string(), suffix)
+}
+
+fn get_exported_decl_names(decl: &Decl) -> Vec<String> {
+  match decl {
+    Decl::Class(class_decl) if !class_decl.declare() => {
+      vec![class_decl.ident.sym().to_string()]
+    }
+    Decl::Fn(fn_decl) if !fn_decl.declare() => {
+      vec![fn_decl.ident.sym().to_string()]
+    }
+    Decl::Var(var_decl) if !var_decl.declare() => {
+      let ids: Vec<Id> = find_ids(var_decl.inner);
+      ids.into_iter().map(|id| id.0.to_string()).collect()
+    }
+    Decl::TsEnum(ts_enum) if !ts_enum.declare() && !ts_enum.is_const() => {
+      vec![ts_enum.id.sym().to_string()]
+    }
+    // interfaces, type aliases, namespaces, and ambient declarations
+    // are left as-is for the TypeScript compiler
+    _ => Vec::new(),
+  }
+}
+
+fn ensure_no_top_level_await(params: &GetCjsTextChangesParams) -> Result<()> {
+  return visit_children(&params.program.into(), false, params);
+
+  fn visit_children(
+    node: &Node,
+    is_in_function: bool,
+    params: &GetCjsTextChangesParams,
+  ) -> Result<()> {
+    let maybe_await_pos = match node {
+      Node::AwaitExpr(await_expr) => Some(await_expr.span().lo),
+      // ex. `for await (const value of values) {}`
+      Node::ForOfStmt(for_of_stmt) if for_of_stmt.is_await() => {
+        Some(for_of_stmt.span().lo)
+      }
+      _ => None,
+    };
+    if let Some(await_pos) = maybe_await_pos {
+      if !is_in_function {
+        let (line, column) =
+          get_line_and_column_display(params.source_text, await_pos);
+        anyhow::bail!(
+          "Top level await is not supported when outputting CommonJS ({}:{}:{}).",
+          params.specifier,
+          line,
+          column,
+        );
+      }
+    }
+
+    let is_in_function = is_in_function
+      || matches!(
+        node,
+        Node::Function(_)
+          | Node::ArrowExpr(_)
+          | Node::Constructor(_)
+          | Node::GetterProp(_)
+          | Node::SetterProp(_)
+      );
+    for child in node.children() {
+      visit_children(&child, is_in_function, params)?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,24 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use anyhow::Result;
+use deno_ast::view::Program;
+use deno_ast::ModuleSpecifier;
+
+use crate::mappings::Mappings;
+use crate::text_changes::TextChange;
+
+pub struct TextChangeVisitorParams<'a> {
+  pub specifier: &'a ModuleSpecifier,
+  pub program: &'a Program<'a>,
+  pub mappings: &'a Mappings,
+}
+
+/// A visitor provided via `TransformOptions` that gets text changes for
+/// each transformed module.
+///
+/// Its text changes must not overlap the built-in text changes (ex. the
+/// changes to module specifiers), otherwise transforming errors. `Deno`
+/// references within them are not shimmed.
+pub trait TextChangeVisitor {
+  fn visit(&self, params: &TextChangeVisitorParams) -> Result<Vec<TextChange>>;
+}
@@ -1,76 +1,247 @@
 // Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
 
+use std::collections::HashMap;
 use std::collections::HashSet;
 
 use deno_ast::swc::common::SyntaxContext;
 use deno_ast::swc::utils::ident::IdentLike;
 use deno_ast::view::*;
+use regex::Regex;
 
+use crate::ignore_directives::IgnoreDirectives;
 use crate::text_changes::TextChange;
+use crate::utils::get_all_ident_names;
+use crate::utils::get_unique_name;
+use crate::utils::is_type_position;
+use crate::ShimImportStyle;
+use crate::TestHarness;
+
+lazy_static! {
+  static ref SIMPLE_EXPR_RE: Regex = Regex::new(
+    r#"^([A-Za-z_$][A-Za-z0-9_$]*(\.[A-Za-z_$][A-Za-z0-9_$]*)*|[0-9]+(\.[0-9]+)?|"[^"]*"|'[^']*')$"#
+  )
+  .unwrap();
+}
 
 pub struct GetDenoGlobalTextChangesParams<'a> {
   pub program: &'a Program<'a>,
   pub top_level_context: SyntaxContext,
-  pub shim_package_name: &'a str,
+  /// Module specifier to import the shim from.
+  pub shim_specifier: &'a str,
+  pub shim_import_style: ShimImportStyle,
+  pub ignore_directives: &'a IgnoreDirectives,
+  /// Text changes of the custom visitors. `Deno` references within
+  /// these are left as-is since the custom changes take precedence.
+  pub custom_text_changes: &'a [TextChange],
+  /// Replacements for member expression paths (ex. `Deno.build.os`).
+  pub defines: &'a HashMap<String, String>,
+  pub is_cjs: bool,
+  pub is_typescript: bool,
+  /// Harness to rewrite `Deno.test` calls to use. This is only provided
+  /// for test modules.
+  pub test_harness: Option<&'a TestHarness>,
+}
+
+pub struct DenoGlobalTextChanges {
+  pub text_changes: Vec<TextChange>,
+  /// Member paths (ex. `Deno.readTextFile`) of the `Deno` references
+  /// that were changed to use the shim, along with the position of
+  /// their `Deno` identifier.
+  pub shimmed_members: Vec<(String, BytePos)>,
+  /// Paths of the `Deno` types that were changed to use the shim
+  /// (ex. `Deno.Reader`).
+  pub shimmed_types: Vec<String>,
+  /// Whether the shim is imported by the module.
+  pub uses_shim: bool,
 }
 
 struct Context<'a> {
   program: &'a Program<'a>,
   top_level_context: SyntaxContext,
+  ignore_directives: &'a IgnoreDirectives,
+  custom_text_changes: &'a [TextChange],
+  defines: &'a HashMap<String, String>,
   has_top_level_deno_decl: bool,
+  is_typescript: bool,
   import_shim: bool,
+  /// Whether the shim is referenced in type positions.
+  import_shim_types: bool,
+  maybe_test_fn: Option<TestFnContext>,
   text_changes: Vec<TextChange>,
+  shimmed_members: Vec<(String, BytePos)>,
+  shimmed_types: Vec<String>,
+}
+
+struct TestFnContext {
+  is_node_test: bool,
+  local_name: String,
+  used: bool,
 }
 
 pub fn get_deno_global_text_changes<'a>(
   params: &GetDenoGlobalTextChangesParams<'a>,
-) -> Vec<TextChange> {
+) -> DenoGlobalTextChanges {
+  if params.ignore_directives.is_file_ignored() {
+    return DenoGlobalTextChanges {
+      text_changes: Vec::new(),
+      shimmed_members: Vec::new(),
+      shimmed_types: Vec::new(),
+      uses_shim: false,
+    };
+  }
+
   let top_level_decls =
     get_top_level_declarations(params.program, params.top_level_context);
+  let program = params.program;
+
+  // currently very crude. This should be improved to only look
+  // at binding declarations
+  let all_ident_names = get_all_ident_names(program);
+  let (deno_name, deno_ref) = match params.shim_import_style {
+    ShimImportStyle::Namespace => {
+      let name = get_unique_name("denoShim", &all_ident_names);
+      let deno_ref = format!("{}.Deno", name);
+      (name, deno_ref)
+    }
+    ShimImportStyle::Named => {
+      let name = get_unique_name("denoShimDeno", &all_ident_names);
+      (name.clone(), name)
+    }
+  };
   let mut context = Context {
-    program: params.program,
+    program,
     top_level_context: params.top_level_context,
+    ignore_directives: params.ignore_directives,
+    custom_text_changes: params.custom_text_changes,
+    defines: params.defines,
     has_top_level_deno_decl: top_level_decls.contains("Deno"),
+    is_typescript: params.is_typescript,
     import_shim: false,
+    import_shim_types: false,
+    maybe_test_fn: params.test_harness.map(|test_harness| TestFnContext {
+      is_node_test: matches!(test_harness, TestHarness::NodeTest),
+      local_name: get_unique_name(test_harness.export_name(), &all_ident_names),
+      used: false,
+    }),
     text_changes: Vec::new(),
+    shimmed_members: Vec::new(),
+    shimmed_types: Vec::new(),
   };
-  let program = params.program;
 
-  // currently very crude. This should be improved to only look
-  // at binding declarations
-  let all_ident_names = get_all_ident_names(context.program);
-  let deno_name = get_unique_name("denoShim", &all_ident_names);
+  visit_children(&program.into(), &deno_ref, &mut context);
 
-  visit_children(&program.into(), &deno_name, &mut context);
+  let mut imports_text = String::new();
+  if context.import_shim || context.import_shim_types {
+    // use a type-only import when `Deno` is only referenced in types so
+    // nothing is imported at runtime
+    let is_type_only = !context.import_shim;
+    imports_text.push_str(&match (
+      params.is_cjs && !is_type_only,
+      params.shim_import_style,
+    ) {
+      (false, ShimImportStyle::Namespace) => format!(
+        "import {}* as {} from \"{}\";\n",
+        if is_type_only { "type " } else { "" },
+        deno_name,
+        params.shim_specifier,
+      ),
+      (false, ShimImportStyle::Named) => format!(
+        "import {}{{ Deno as {} }} from \"{}\";\n",
+        if is_type_only { "type " } else { "" },
+        deno_name,
+        params.shim_specifier,
+      ),
+      (true, ShimImportStyle::Namespace) => format!(
+        "const {} = require(\"{}\");\n",
+        deno_name, params.shim_specifier,
+      ),
+      (true, ShimImportStyle::Named) => format!(
+        "const {{ Deno: {} }} = require(\"{}\");\n",
+        deno_name, params.shim_specifier,
+      ),
+    });
+  }
+  if let Some(test_fn) = context.maybe_test_fn.as_ref().filter(|t| t.used) {
+    // the harness is always provided along with the test function context
+    let test_harness = params.test_harness.unwrap();
+    let export_name = test_harness.export_name();
+    imports_text.push_str(&match (
+      params.is_cjs,
+      export_name == test_fn.local_name,
+    ) {
+      (false, true) => format!(
+        "import {{ {} }} from \"{}\";\n",
+        export_name,
+        test_harness.module(),
+      ),
+      (false, false) => format!(
+        "import {{ {} as {} }} from \"{}\";\n",
+        export_name,
+        test_fn.local_name,
+        test_harness.module(),
+      ),
+      (true, true) => format!(
+        "const {{ {} }} = require(\"{}\");\n",
+        export_name,
+        test_harness.module(),
+      ),
+      (true, false) => format!(
+        "const {{ {}: {} }} = require(\"{}\");\n",
+        export_name,
+        test_fn.local_name,
+        test_harness.module(),
+      ),
+    });
+  }
 
-  if context.import_shim {
+  if !imports_text.is_empty() {
     context.text_changes.push(TextChange {
       span: Span::new(BytePos(0), BytePos(0), Default::default()),
-      new_text: format!(
-        "import * as {} from \"{}\";\n",
-        deno_name, params.shim_package_name,
-      ),
+      new_text: imports_text,
     });
   }
 
-  context.text_changes
+  DenoGlobalTextChanges {
+    text_changes: context.text_changes,
+    shimmed_members: context.shimmed_members,
+    shimmed_types: context.shimmed_types,
+    uses_shim: context.import_shim || context.import_shim_types,
+  }
 }
 
-fn visit_children(node: &Node, import_name: &str, context: &mut Context) {
+fn visit_children(node: &Node, deno_ref: &str, context: &mut Context) {
   for child in node.children() {
-    visit_children(&child, import_name, context);
+    visit_children(&child, deno_ref, context);
   }
 
   if let Node::Ident(ident) = node {
+    if context.ignore_directives.is_ignored(ident.span().lo) {
+      return;
+    }
+    let span = ident.span();
+    if context.custom_text_changes.iter().any(|text_change| {
+      text_change.span.lo < span.hi && span.lo < text_change.span.hi
+    }) {
+      return;
+    }
+
     let id = ident.inner.to_id();
     let is_top_level_context = id.1 == context.top_level_context;
     let ident_text = ident.text_fast(context.program);
     if is_top_level_context && ident_text == "globalThis" {
       context.text_changes.push(TextChange {
         span: ident.span(),
-        new_text: format!("({{ Deno: {}.Deno, ...globalThis }})", import_name),
+        new_text: format!("({{ Deno: {}, ...globalThis }})", deno_ref),
       });
       context.import_shim = true;
+      let member_paths = get_member_paths(node, "globalThis");
+      if member_paths.len() > 1 && member_paths[1].0 == "globalThis.Deno" {
+        let (member_path, _) = member_paths.last().unwrap();
+        context.shimmed_members.push((
+          member_path["globalThis.".len()..].to_string(),
+          ident.span().lo,
+        ));
+      }
     }
 
     // check if Deno should be imported
@@ -78,13 +249,181 @@ fn visit_children(node: &Node, import_name: &str, context: &mut Context) {
       && !context.has_top_level_deno_decl
       && ident_text == "Deno"
     {
+      if is_type_position(node) {
+        context.text_changes.push(TextChange {
+          span: ident.span(),
+          new_text: deno_ref.to_string(),
+        });
+        context.import_shim_types = true;
+        if let Some(type_path) = get_type_path(node) {
+          context.shimmed_types.push(type_path);
+        }
+        return;
+      }
+
+      if let Some((span, replacement)) = get_define(node, context.defines) {
+        context.text_changes.push(TextChange {
+          span,
+          new_text: if SIMPLE_EXPR_RE.is_match(replacement) {
+            replacement.to_string()
+          } else {
+            format!("({})", replacement)
+          },
+        });
+        return;
+      }
+
+      if let Some(test_fn) = context.maybe_test_fn.as_mut() {
+        if let Some((member_expr, call_expr)) = get_deno_test_call(node) {
+          let any_type = if context.is_typescript { ": any" } else { "" };
+          let new_text = match call_expr.args.get(0) {
+            Some(arg) if test_fn.is_node_test && call_expr.args.len() == 1 => {
+              match arg.expr {
+                // convert the object form to node:test's (name, options, fn)
+                Expr::Object(_) => format!(
+                  "((t{}) => {}(t.name, {{ skip: t.ignore, only: t.only }}, t.fn))",
+                  any_type, test_fn.local_name,
+                ),
+                // named function form (ex. `Deno.test(function myTest() {})`)
+                _ => format!(
+                  "((fn{}) => {}(fn.name, fn))",
+                  any_type, test_fn.local_name,
+                ),
+              }
+            }
+            _ => test_fn.local_name.clone(),
+          };
+          context.text_changes.push(TextChange {
+            span: member_expr.span(),
+            new_text,
+          });
+          test_fn.used = true;
+          return;
+        }
+      }
+
       context.text_changes.push(TextChange {
         span: ident.span(),
-        new_text: format!("{}.Deno", import_name),
+        new_text: deno_ref.to_string(),
       });
       context.import_shim = true;
+      let (member_path, _) = get_member_paths(node, "Deno").pop().unwrap();
+      context.shimmed_members.push((member_path, ident.span().lo));
+    }
+  }
+}
+
+/// Gets the path of the type referenced by the qualified name starting at
+/// the provided `Deno` identifier (ex. `Deno.errors.NotFound`).
+///
+/// Returns `None` for type queries (ex. `typeof Deno.build`) since those
+/// reference values.
+fn get_type_path(node: &Node) -> Option<String> {
+  let mut path = "Deno".to_string();
+  let mut current = *node;
+  loop {
+    match current.parent() {
+      Some(Node::TsQualifiedName(qualified_name)) => {
+        path.push('.');
+        path.push_str(qualified_name.right.sym());
+        current = Node::TsQualifiedName(qualified_name);
+      }
+      Some(Node::TsTypeQuery(_)) => return None,
+      _ => return Some(path),
+    }
+  }
+}
+
+/// Gets the span and replacement of the longest member expression path
+/// starting at the provided `Deno` identifier that has a define.
+///
+/// Paths that are assigned to (ex. `Deno.env.X = 1`) are not replaced.
+fn get_define<'a>(
+  node: &Node,
+  defines: &'a HashMap<String, String>,
+) -> Option<(Span, &'a str)> {
+  if defines.is_empty() {
+    return None;
+  }
+
+  let (path_node, replacement) = get_member_paths(node, "Deno")
+    .into_iter()
+    .rev()
+    .find_map(|(path, path_node)| {
+      defines
+        .get(&path)
+        .map(|replacement| (path_node, replacement.as_str()))
+    })?;
+  if is_assignment_target(&path_node) {
+    None
+  } else {
+    Some((path_node.span(), replacement))
+  }
+}
+
+/// Gets if the node is assigned to by an assignment or update
+/// expression (ex. `X = 1` or `X++`).
+fn is_assignment_target(node: &Node) -> bool {
+  match node.parent() {
+    Some(Node::AssignExpr(assign_expr)) => {
+      assign_expr.right.span() != node.span()
+    }
+    Some(Node::UpdateExpr(_)) => true,
+    _ => false,
+  }
+}
+
+/// Gets the non-computed member expression paths starting at the provided
+/// identifier from shortest to longest (ex. `Deno`, `Deno.build`,
+/// `Deno.build.os`) along with their nodes.
+fn get_member_paths<'a>(
+  node: &Node<'a>,
+  ident_text: &str,
+) -> Vec<(String, Node<'a>)> {
+  let mut path = ident_text.to_string();
+  let mut paths = vec![(path.clone(), *node)];
+  let mut current = *node;
+  while let Some(Node::MemberExpr(member_expr)) = current.parent() {
+    if member_expr.computed() || member_expr.obj.span() != current.span() {
+      break;
     }
+    match &member_expr.prop {
+      Expr::Ident(prop) => {
+        path.push('.');
+        path.push_str(prop.sym());
+      }
+      _ => break,
+    }
+    paths.push((path.clone(), Node::MemberExpr(member_expr)));
+    current = Node::MemberExpr(member_expr);
   }
+  paths
+}
+
+/// Gets the member and call expressions of a `Deno.test(...)` call
+/// when the provided node is its `Deno` identifier.
+fn get_deno_test_call<'a>(
+  node: &Node<'a>,
+) -> Option<(&'a MemberExpr<'a>, &'a CallExpr<'a>)> {
+  let member_expr = match node.parent()? {
+    Node::MemberExpr(member_expr) => member_expr,
+    _ => return None,
+  };
+  if member_expr.computed() || member_expr.obj.span() != node.span() {
+    return None;
+  }
+  match &member_expr.prop {
+    Expr::Ident(prop) if &**prop.sym() == "test" => {}
+    _ => return None,
+  }
+  let call_expr = match Node::MemberExpr(member_expr).parent()? {
+    Node::CallExpr(call_expr) => call_expr,
+    _ => return None,
+  };
+  if call_expr.callee.span() != member_expr.span() {
+    return None;
+  }
+  Some((member_expr, call_expr))
 }
 
 fn get_top_level_declarations(
@@ -105,29 +444,3 @@ fn get_top_level_declarations(
   };
   results.iter().map(|v| v.0.to_string()).collect()
 }
-
-fn get_all_ident_names(program: &Program) -> HashSet<String> {
-  let mut result = HashSet::new();
-  visit_children(&program.into(), &mut result);
-  return result;
-
-  fn visit_children(node: &Node, result: &mut HashSet<String>) {
-    for child in node.children() {
-      visit_children(&child, result);
-    }
-
-    if let Node::Ident(ident) = node {
-      result.insert(ident.sym().to_string());
-    }
-  }
-}
-
-fn get_unique_name(name: &str, all_idents: &HashSet<String>) -> String {
-  let mut count = 0;
-  let mut new_name = name.to_string();
-  while all_idents.contains(&new_name) {
-    count += 1;
-    new_name = format!("{}{}", name, count);
-  }
-  new_name
-}
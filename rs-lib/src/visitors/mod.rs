@@ -1,7 +1,13 @@
 // Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
 
+mod cjs;
+mod custom;
 mod deno_globals;
 mod module_specifiers;
+mod type_directives;
 
+pub use cjs::*;
+pub use custom::*;
 pub use deno_globals::*;
 pub use module_specifiers::*;
+pub use type_directives::*;
@@ -2,101 +2,373 @@
 
 use std::path::PathBuf;
 
+use anyhow::Result;
 use deno_ast::swc::common::BytePos;
 use deno_ast::swc::common::Span;
 use deno_ast::view::*;
 use deno_ast::ModuleSpecifier;
-use deno_graph::ModuleGraph;
 
+use crate::graph::ModuleGraph;
+use crate::ignore_directives::IgnoreDirectives;
+use crate::jsdoc_imports::JsDocImport;
 use crate::mappings::Mappings;
 use crate::text_changes::TextChange;
-use crate::utils::get_relative_path;
+use crate::utils::get_relative_specifier;
+use crate::utils::is_std_asserts_specifier;
+use crate::External;
+use crate::OutputDependency;
+use crate::OutputDependencyKind;
+use crate::SpecifierExtension;
 
 pub struct GetModuleSpecifierTextChangesParams<'a> {
   pub specifier: &'a ModuleSpecifier,
   pub module_graph: &'a ModuleGraph,
-  pub use_js_extension: bool,
+  pub specifier_extension: SpecifierExtension,
   pub mappings: &'a Mappings,
   pub program: &'a Program<'a>,
+  /// Import types found in JSDoc comments, which are only
+  /// rewritten in JavaScript modules.
+  pub jsdoc_imports: &'a [JsDocImport],
+  /// Module to use in place of imports of the Deno standard
+  /// library's `testing/asserts.ts` module.
+  pub assertion_module: Option<&'a str>,
+  pub externals: &'a [External],
+  pub ignore_directives: &'a IgnoreDirectives,
 }
 
 struct Context<'a> {
   specifier: &'a ModuleSpecifier,
   module_graph: &'a ModuleGraph,
-  use_js_extension: bool,
+  specifier_extension: SpecifierExtension,
   mappings: &'a Mappings,
+  assertion_module: Option<&'a str>,
+  externals: &'a [External],
+  ignore_directives: &'a IgnoreDirectives,
   output_file_path: &'a PathBuf,
   text_changes: Vec<TextChange>,
+  dependencies: Vec<OutputDependency>,
+}
+
+pub struct ModuleSpecifierTextChanges {
+  pub text_changes: Vec<TextChange>,
+  /// The dependencies of the module along with their rewritten specifiers.
+  pub dependencies: Vec<OutputDependency>,
 }
 
 pub fn get_module_specifier_text_changes<'a>(
   params: &GetModuleSpecifierTextChangesParams<'a>,
-) -> Vec<TextChange> {
+) -> Result<ModuleSpecifierTextChanges> {
   let mut context = Context {
     specifier: params.specifier,
     module_graph: params.module_graph,
-    use_js_extension: params.use_js_extension,
+    specifier_extension: params.specifier_extension,
     mappings: params.mappings,
+    assertion_module: params.assertion_module,
+    externals: params.externals,
+    ignore_directives: params.ignore_directives,
     output_file_path: params.mappings.get_file_path(params.specifier),
     text_changes: Vec::new(),
+    dependencies: Vec::new(),
   };
 
-  // todo: look at imports in ts namespaces? I forget if they support importing from another module and if that works in Deno
   for child in params.program.children() {
     match child {
       Node::ImportDecl(import_decl) => {
-        visit_module_specifier(&import_decl.src, &mut context);
+        visit_module_specifier(
+          &import_decl.src,
+          import_decl.span().lo,
+          get_static_kind(import_decl.type_only()),
+          false,
+          &mut context,
+        )?;
       }
       Node::ExportAll(export_all) => {
-        visit_module_specifier(&export_all.src, &mut context);
+        visit_module_specifier(
+          &export_all.src,
+          export_all.span().lo,
+          OutputDependencyKind::Static,
+          false,
+          &mut context,
+        )?;
       }
       Node::NamedExport(named_export) => {
         if let Some(src) = named_export.src.as_ref() {
-          visit_module_specifier(src, &mut context);
+          visit_module_specifier(
+            src,
+            named_export.span().lo,
+            get_static_kind(named_export.type_only()),
+            false,
+            &mut context,
+          )?;
+        }
+      }
+      // ex. `import fs = require("./fs.ts");`
+      Node::TsImportEqualsDecl(import_equals) => {
+        if let TsModuleRef::TsExternalModuleRef(module_ref) =
+          &import_equals.module_ref
+        {
+          visit_module_specifier(
+            &module_ref.expr,
+            import_equals.span().lo,
+            OutputDependencyKind::Static,
+            false,
+            &mut context,
+          )?;
         }
       }
       _ => {}
     }
   }
 
-  context.text_changes
+  visit_nested_imports(&params.program.into(), &mut context)?;
+
+  for jsdoc_import in params.jsdoc_imports {
+    // these aren't dependencies in the module graph, so resolve them manually
+    // and leave the ones that aren't output as-is
+    let specifier = match params.specifier.join(&jsdoc_import.value) {
+      Ok(specifier) => params.module_graph.resolve(&specifier),
+      Err(_) => continue,
+    };
+    if params.mappings.try_get_file_path(&specifier).is_none() {
+      continue;
+    }
+    if params.ignore_directives.is_ignored(jsdoc_import.span.lo) {
+      push_unchanged_dependency(
+        &jsdoc_import.value,
+        Some(specifier),
+        OutputDependencyKind::Types,
+        &mut context,
+      );
+      continue;
+    }
+    visit_specifier_value(
+      &jsdoc_import.value,
+      jsdoc_import.span,
+      Some(specifier),
+      OutputDependencyKind::Types,
+      false,
+      &mut context,
+    )?;
+  }
+
+  Ok(ModuleSpecifierTextChanges {
+    text_changes: context.text_changes,
+    dependencies: context.dependencies,
+  })
+}
+
+fn get_static_kind(type_only: bool) -> OutputDependencyKind {
+  if type_only {
+    OutputDependencyKind::Types
+  } else {
+    OutputDependencyKind::Static
+  }
+}
+
+/// Visits the dynamic imports and import types (ex. `import("./mod.ts").Type`)
+/// found anywhere in the program, including within namespaces.
+fn visit_nested_imports(node: &Node, context: &mut Context) -> Result<()> {
+  for child in node.children() {
+    visit_nested_imports(&child, context)?;
+  }
+
+  match node {
+    // imports of modules that aren't output (ex. `node:fs`) are left as-is
+    // since they may only be imported at runtime or only provide types
+    Node::CallExpr(call_expr) => {
+      if let Some(src) = get_dynamic_import_str(call_expr) {
+        visit_module_specifier(
+          src,
+          call_expr.span().lo,
+          OutputDependencyKind::Dynamic,
+          true,
+          context,
+        )?;
+      }
+    }
+    Node::TsImportType(import_type) => {
+      visit_module_specifier(
+        &import_type.arg,
+        import_type.span().lo,
+        OutputDependencyKind::Types,
+        true,
+        context,
+      )?;
+    }
+    _ => {}
+  }
+
+  Ok(())
 }
 
-fn visit_module_specifier(str: &Str, context: &mut Context) {
+/// Gets the string literal specifier of a statically analyzable
+/// dynamic import (ex. `import("./mod.ts")`).
+pub fn get_dynamic_import_str<'a>(
+  call_expr: &CallExpr<'a>,
+) -> Option<&'a Str<'a>> {
+  let is_import_callee = match &call_expr.callee {
+    ExprOrSuper::Expr(Expr::Ident(ident)) => &**ident.sym() == "import",
+    _ => false,
+  };
+  if !is_import_callee || call_expr.args.len() != 1 {
+    return None;
+  }
+  match &call_expr.args[0].expr {
+    Expr::Lit(Lit::Str(str)) => Some(*str),
+    _ => None,
+  }
+}
+
+/// Visits the module specifier of the statement or expression starting
+/// at the provided position.
+///
+/// Specifiers that don't resolve to an output file are left as-is when
+/// `keep_unmapped` is true. Otherwise they error.
+fn visit_module_specifier(
+  str: &Str,
+  start_pos: BytePos,
+  kind: OutputDependencyKind,
+  keep_unmapped: bool,
+  context: &mut Context,
+) -> Result<()> {
   let value = str.value().to_string();
-  let specifier = match context
+  let maybe_specifier = context
     .module_graph
-    .resolve_dependency(&value, &context.specifier)
+    .resolve_dependency(&value, &context.specifier);
+  if context.ignore_directives.is_ignored(start_pos) {
+    push_unchanged_dependency(&value, maybe_specifier, kind, context);
+    return Ok(());
+  }
+  visit_specifier_value(
+    &value,
+    get_specifier_text_span(str),
+    maybe_specifier,
+    kind,
+    keep_unmapped,
+    context,
+  )
+}
+
+/// Rewrites the specifier text at the provided span based on what
+/// it resolved to.
+fn visit_specifier_value(
+  value: &str,
+  span: Span,
+  maybe_specifier: Option<ModuleSpecifier>,
+  kind: OutputDependencyKind,
+  keep_unmapped: bool,
+  context: &mut Context,
+) -> Result<()> {
+  let resolved_text = maybe_specifier
+    .as_ref()
+    .map(|specifier| specifier.as_str())
+    .unwrap_or(value);
+  if let Some(external) = context
+    .externals
+    .iter()
+    .find(|external| external.matches(resolved_text))
   {
+    let maybe_new_text = external.get_replacement(resolved_text);
+    context.dependencies.push(OutputDependency {
+      specifier: value.to_string(),
+      rewritten_specifier: maybe_new_text
+        .clone()
+        .unwrap_or_else(|| value.to_string()),
+      resolved_specifier: maybe_specifier.as_ref().map(|s| s.to_string()),
+      kind,
+    });
+    if let Some(new_text) = maybe_new_text {
+      context.text_changes.push(TextChange { span, new_text });
+    }
+    return Ok(());
+  }
+  let specifier = match maybe_specifier {
     Some(specifier) => specifier,
-    // todo: error instead of panic
-    None => panic!("Could not resolve specifier: {}", value),
+    None if keep_unmapped => {
+      push_unchanged_dependency(value, None, kind, context);
+      return Ok(());
+    }
+    None => anyhow::bail!(
+      "Could not resolve specifier {} in {}.",
+      value,
+      context.specifier,
+    ),
   };
-  let specifier_file_path = context.mappings.get_file_path(&specifier);
-  let relative_path =
-    get_relative_path(context.output_file_path, specifier_file_path);
-  let relative_path_str = if context.use_js_extension {
-    relative_path.with_extension("js")
-  } else {
-    relative_path.with_extension("")
+  if let Some(assertion_module) = context.assertion_module {
+    if is_std_asserts_specifier(&specifier) {
+      push_text_change(
+        value,
+        span,
+        &specifier,
+        assertion_module.to_string(),
+        kind,
+        context,
+      );
+      return Ok(());
+    }
   }
-  .to_string_lossy()
-  .to_string()
-  .replace("\\", "/");
-  let new_text = if relative_path_str.starts_with("../")
-    || relative_path_str.starts_with("./")
+  let specifier_file_path = match context.mappings.try_get_file_path(&specifier)
   {
-    relative_path_str
-  } else {
-    format!("./{}", relative_path_str)
+    Some(file_path) => file_path,
+    None if keep_unmapped => {
+      push_unchanged_dependency(value, Some(specifier), kind, context);
+      return Ok(());
+    }
+    None => anyhow::bail!(
+      "Could not find an output file for {} imported in {}.",
+      specifier,
+      context.specifier,
+    ),
   };
+  let new_text = get_relative_specifier(
+    context.output_file_path,
+    context
+      .specifier_extension
+      .get_specifier_path(specifier_file_path),
+  );
 
-  context.text_changes.push(TextChange {
-    span: Span::new(
-      str.span().lo + BytePos(1),
-      str.span().hi - BytePos(1),
-      Default::default(),
-    ),
-    new_text,
+  push_text_change(value, span, &specifier, new_text, kind, context);
+  Ok(())
+}
+
+fn push_text_change(
+  value: &str,
+  span: Span,
+  specifier: &ModuleSpecifier,
+  new_text: String,
+  kind: OutputDependencyKind,
+  context: &mut Context,
+) {
+  context.dependencies.push(OutputDependency {
+    specifier: value.to_string(),
+    rewritten_specifier: new_text.clone(),
+    resolved_specifier: Some(specifier.to_string()),
+    kind,
   });
+  context.text_changes.push(TextChange { span, new_text });
+}
+
+/// Records the dependency of a specifier that's left as-is (ex. because
+/// of a `dnt-ignore` directive).
+fn push_unchanged_dependency(
+  value: &str,
+  maybe_specifier: Option<ModuleSpecifier>,
+  kind: OutputDependencyKind,
+  context: &mut Context,
+) {
+  context.dependencies.push(OutputDependency {
+    specifier: value.to_string(),
+    rewritten_specifier: value.to_string(),
+    resolved_specifier: maybe_specifier.map(|s| s.to_string()),
+    kind,
+  });
+}
+
+/// Gets the span of the text within the quotes of a string literal.
+fn get_specifier_text_span(str: &Str) -> Span {
+  Span::new(
+    str.span().lo + BytePos(1),
+    str.span().hi - BytePos(1),
+    Default::default(),
+  )
 }
@@ -0,0 +1,144 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use deno_ast::swc::common::comments::Comment;
+use deno_ast::swc::common::BytePos;
+use deno_ast::swc::common::Span;
+use deno_ast::ModuleSpecifier;
+use regex::Regex;
+
+use crate::ignore_directives::IgnoreDirectives;
+use crate::mappings::Mappings;
+use crate::text_changes::TextChange;
+use crate::utils::get_relative_specifier;
+use crate::SpecifierExtension;
+use crate::TypeDirectiveMode;
+
+lazy_static! {
+  static ref DENO_TYPES_RE: Regex =
+    Regex::new(r#"^\s*@deno-types\s*=\s*(?:["']([^"']+)["']|(\S+))"#).unwrap();
+  static ref REFERENCE_RE: Regex = Regex::new(
+    r#"^/\s*<reference\s+(types|path|lib)\s*=\s*["']([^"']+)["']\s*/>"#
+  )
+  .unwrap();
+}
+
+pub struct GetTypeDirectiveTextChangesParams<'a> {
+  pub specifier: &'a ModuleSpecifier,
+  pub mappings: &'a Mappings,
+  /// Used to point the directives at the files' output extensions
+  /// (ex. `mod.d.mts` when using `.mjs` specifiers).
+  pub specifier_extension: SpecifierExtension,
+  pub comments: &'a [Comment],
+  pub source_text: &'a str,
+  pub mode: TypeDirectiveMode,
+  pub ignore_directives: &'a IgnoreDirectives,
+}
+
+/// Gets the text changes to rewrite or remove `@deno-types` comments
+/// and triple-slash reference directives.
+///
+/// Deno specific lib references (ex. `/// <reference lib="deno.ns" />`)
+/// are always removed. Directives ignored by `dnt-ignore` directives
+/// are left as-is.
+pub fn get_type_directive_text_changes<'a>(
+  params: &GetTypeDirectiveTextChangesParams<'a>,
+) -> Vec<TextChange> {
+  let mut text_changes = Vec::new();
+  let output_file_path = params.mappings.get_file_path(params.specifier);
+
+  for comment in params.comments.iter() {
+    if params.ignore_directives.is_ignored(comment.span.lo) {
+      continue;
+    }
+    // skip over the leading `//` or `/*`
+    let text_start = comment.span.lo + BytePos(2);
+    let (value, value_start) =
+      if let Some(captures) = DENO_TYPES_RE.captures(&comment.text) {
+        let value = captures.get(1).or_else(|| captures.get(2)).unwrap();
+        (value.as_str(), value.start())
+      } else if let Some(captures) = REFERENCE_RE.captures(&comment.text) {
+        let value = captures.get(2).unwrap();
+        if &captures[1] == "lib" {
+          if value.as_str().starts_with("deno.") {
+            text_changes.push(get_remove_comment_text_change(
+              comment.span,
+              params.source_text,
+            ));
+          }
+          continue;
+        }
+        (value.as_str(), value.start())
+      } else {
+        continue;
+      };
+
+    if !is_relative_or_url(value) {
+      // ex. `/// <reference types="node" />`
+      continue;
+    }
+
+    let maybe_file_path = params
+      .specifier
+      .join(value)
+      .ok()
+      .and_then(|specifier| params.mappings.try_get_file_path(&specifier));
+    match (params.mode, maybe_file_path) {
+      (TypeDirectiveMode::Rewrite, Some(file_path)) => {
+        let value_lo = text_start + BytePos(value_start as u32);
+        text_changes.push(TextChange {
+          span: Span::new(
+            value_lo,
+            value_lo + BytePos(value.len() as u32),
+            Default::default(),
+          ),
+          new_text: get_relative_specifier(
+            output_file_path,
+            params
+              .specifier_extension
+              .get_output_file_path(file_path)
+              .unwrap_or_else(|| file_path.to_path_buf()),
+          ),
+        });
+      }
+      // remove when the referenced file is not output since the
+      // directive would otherwise point at nothing
+      _ => {
+        text_changes.push(get_remove_comment_text_change(
+          comment.span,
+          params.source_text,
+        ));
+      }
+    }
+  }
+
+  text_changes
+}
+
+fn is_relative_or_url(value: &str) -> bool {
+  value.starts_with("./")
+    || value.starts_with("../")
+    || value.starts_with('/')
+    || ModuleSpecifier::parse(value).is_ok()
+}
+
+/// Removes the comment along with its line when nothing else is on it.
+fn get_remove_comment_text_change(span: Span, source_text: &str) -> TextChange {
+  let lo = span.lo.0 as usize;
+  let hi = span.hi.0 as usize;
+  let line_start = source_text[..lo].rfind('\n').map(|i| i + 1).unwrap_or(0);
+  let line_end = source_text[hi..]
+    .find('\n')
+    .map(|i| hi + i + 1)
+    .unwrap_or_else(|| source_text.len());
+  let is_only_on_line = source_text[line_start..lo].trim().is_empty()
+    && source_text[hi..line_end].trim().is_empty();
+  let (lo, hi) = if is_only_on_line {
+    (line_start, line_end)
+  } else {
+    (lo, hi)
+  };
+  TextChange {
+    span: Span::new(BytePos(lo as u32), BytePos(hi as u32), Default::default()),
+    new_text: String::new(),
+  }
+}
@@ -20,14 +20,17 @@ macro_rules! assert_files {
       );
     }
     actual.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+    let actual = actual
+      .iter()
+      .map(|file| (file.file_path.clone(), file.file_text.clone()))
+      .collect::<Vec<_>>();
     let mut expected = expected
       .iter()
-      .map(|(file_path, file_text)| deno_node_transform::OutputFile {
-        file_path: PathBuf::from(file_path),
-        file_text: file_text.to_string(),
+      .map(|(file_path, file_text)| {
+        (PathBuf::from(file_path), file_text.to_string())
       })
       .collect::<Vec<_>>();
-    expected.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+    expected.sort_by(|a, b| a.0.cmp(&b.0));
 
     pretty_assertions::assert_eq!(actual, expected);
   }};
@@ -1,16 +1,47 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
 use anyhow::Result;
 use deno_node_transform::transform;
+use deno_node_transform::BundleOptions;
+use deno_node_transform::External;
+use deno_node_transform::ModuleFormat;
 use deno_node_transform::ModuleSpecifier;
-use deno_node_transform::OutputFile;
+use deno_node_transform::Shim;
+use deno_node_transform::ShimImportStyle;
+use deno_node_transform::SourceHeaderOptions;
+use deno_node_transform::SpecifierExtension;
+use deno_node_transform::TestHarness;
+use deno_node_transform::TestOptions;
+use deno_node_transform::TextChange;
+use deno_node_transform::TextChangeVisitor;
+use deno_node_transform::TextChangeVisitorParams;
+use deno_node_transform::ThirdPartyNoticesOptions;
 use deno_node_transform::TransformOptions;
+use deno_node_transform::TransformOutput;
+use deno_node_transform::TypeDirectiveMode;
 
 use super::InMemoryLoader;
 
 pub struct TestBuilder {
   loader: InMemoryLoader,
-  keep_extensions: bool,
+  specifier_extension: SpecifierExtension,
+  module_format: ModuleFormat,
+  type_directives: TypeDirectiveMode,
   entry_point: String,
-  shim_package_name: Option<String>,
+  shim: Shim,
+  shim_import_style: ShimImportStyle,
+  test_entry_points: Vec<String>,
+  test_harness: TestHarness,
+  assertion_module: Option<String>,
+  bundle: Option<BundleOptions>,
+  types_overrides: HashMap<String, String>,
+  defines: HashMap<String, String>,
+  supported_deno_apis: Option<Vec<String>>,
+  externals: Vec<External>,
+  visitors: Vec<Rc<dyn TextChangeVisitor>>,
+  fetch_license_files: Option<bool>,
+  source_headers_include_hash: Option<bool>,
 }
 
 impl TestBuilder {
@@ -18,9 +49,23 @@ impl TestBuilder {
     let loader = InMemoryLoader::new();
     Self {
       loader,
-      keep_extensions: false,
+      specifier_extension: SpecifierExtension::None,
+      module_format: ModuleFormat::Esm,
+      type_directives: TypeDirectiveMode::Rewrite,
       entry_point: "file:///mod.ts".to_string(),
-      shim_package_name: None,
+      shim: Shim::Package("shim-package-name".to_string()),
+      shim_import_style: ShimImportStyle::Namespace,
+      test_entry_points: Vec::new(),
+      test_harness: TestHarness::NodeTest,
+      assertion_module: None,
+      bundle: None,
+      types_overrides: HashMap::new(),
+      defines: HashMap::new(),
+      supported_deno_apis: None,
+      externals: Vec::new(),
+      visitors: Vec::new(),
+      fetch_license_files: None,
+      source_headers_include_hash: None,
     }
   }
 
@@ -32,8 +77,21 @@ impl TestBuilder {
     self
   }
 
-  pub fn keep_extensions(&mut self) -> &mut Self {
-    self.keep_extensions = true;
+  pub fn specifier_extension(
+    &mut self,
+    value: SpecifierExtension,
+  ) -> &mut Self {
+    self.specifier_extension = value;
+    self
+  }
+
+  pub fn module_format(&mut self, value: ModuleFormat) -> &mut Self {
+    self.module_format = value;
+    self
+  }
+
+  pub fn type_directives(&mut self, value: TypeDirectiveMode) -> &mut Self {
+    self.type_directives = value;
     self
   }
 
@@ -43,17 +101,166 @@ impl TestBuilder {
   }
 
   pub fn shim_package_name(&mut self, name: impl AsRef<str>) -> &mut Self {
-    self.shim_package_name = Some(name.as_ref().to_string());
+    self.shim = Shim::Package(name.as_ref().to_string());
+    self
+  }
+
+  pub fn shim_import_style(&mut self, value: ShimImportStyle) -> &mut Self {
+    self.shim_import_style = value;
+    self
+  }
+
+  pub fn local_shim(&mut self) -> &mut Self {
+    self.shim = Shim::Local;
+    self
+  }
+
+  pub fn add_test_entry_point(&mut self, value: impl AsRef<str>) -> &mut Self {
+    self.test_entry_points.push(value.as_ref().to_string());
+    self
+  }
+
+  pub fn test_harness(&mut self, value: TestHarness) -> &mut Self {
+    self.test_harness = value;
+    self
+  }
+
+  pub fn assertion_module(&mut self, value: impl AsRef<str>) -> &mut Self {
+    self.assertion_module = Some(value.as_ref().to_string());
+    self
+  }
+
+  pub fn bundle(&mut self, keep_bare_externals: bool) -> &mut Self {
+    self.bundle = Some(BundleOptions {
+      keep_bare_externals,
+    });
+    self
+  }
+
+  pub fn add_types_override(
+    &mut self,
+    specifier: impl AsRef<str>,
+    types_specifier: impl AsRef<str>,
+  ) -> &mut Self {
+    self.types_overrides.insert(
+      specifier.as_ref().to_string(),
+      types_specifier.as_ref().to_string(),
+    );
     self
   }
 
-  pub async fn transform(&self) -> Result<Vec<OutputFile>> {
+  pub fn add_define(
+    &mut self,
+    path: impl AsRef<str>,
+    replacement: impl AsRef<str>,
+  ) -> &mut Self {
+    self
+      .defines
+      .insert(path.as_ref().to_string(), replacement.as_ref().to_string());
+    self
+  }
+
+  pub fn supported_deno_apis(&mut self, apis: &[&str]) -> &mut Self {
+    self.supported_deno_apis =
+      Some(apis.iter().map(|api| api.to_string()).collect());
+    self
+  }
+
+  pub fn add_external(
+    &mut self,
+    prefix: impl AsRef<str>,
+    replacement: Option<&str>,
+  ) -> &mut Self {
+    self.externals.push(External {
+      prefix: prefix.as_ref().to_string(),
+      replacement: replacement.map(ToOwned::to_owned),
+    });
+    self
+  }
+
+  pub fn add_visitor(
+    &mut self,
+    visitor: impl TextChangeVisitor + 'static,
+  ) -> &mut Self {
+    self.visitors.push(Rc::new(visitor));
+    self
+  }
+
+  pub fn third_party_notices(
+    &mut self,
+    fetch_license_files: bool,
+  ) -> &mut Self {
+    self.fetch_license_files = Some(fetch_license_files);
+    self
+  }
+
+  pub fn source_headers(&mut self, include_hash: bool) -> &mut Self {
+    self.source_headers_include_hash = Some(include_hash);
+    self
+  }
+
+  pub async fn transform(&self) -> Result<TransformOutput> {
     transform(TransformOptions {
       entry_point: ModuleSpecifier::parse(&self.entry_point).unwrap(),
-      keep_extensions: self.keep_extensions,
-      shim_package_name: self.shim_package_name.as_ref().map(ToOwned::to_owned),
+      specifier_extension: self.specifier_extension,
+      module_format: self.module_format,
+      type_directives: self.type_directives,
+      shim: self.shim.clone(),
+      shim_import_style: self.shim_import_style,
       loader: Some(Box::new(self.loader.clone())),
+      test: if self.test_entry_points.is_empty() {
+        None
+      } else {
+        Some(TestOptions {
+          entry_points: self
+            .test_entry_points
+            .iter()
+            .map(|s| ModuleSpecifier::parse(s).unwrap())
+            .collect(),
+          harness: self.test_harness.clone(),
+          assertion_module: self.assertion_module.clone(),
+        })
+      },
+      bundle: self.bundle.as_ref().map(|bundle| BundleOptions {
+        keep_bare_externals: bundle.keep_bare_externals,
+      }),
+      types_overrides: self
+        .types_overrides
+        .iter()
+        .map(|(k, v)| {
+          (
+            ModuleSpecifier::parse(k).unwrap(),
+            ModuleSpecifier::parse(v).unwrap(),
+          )
+        })
+        .collect(),
+      defines: self.defines.clone(),
+      supported_deno_apis: self.supported_deno_apis.clone(),
+      externals: self.externals.clone(),
+      visitors: self
+        .visitors
+        .iter()
+        .map(|visitor| {
+          Box::new(SharedVisitor(visitor.clone())) as Box<dyn TextChangeVisitor>
+        })
+        .collect(),
+      third_party_notices: self.fetch_license_files.map(
+        |fetch_license_files| ThirdPartyNoticesOptions {
+          fetch_license_files,
+        },
+      ),
+      source_headers: self
+        .source_headers_include_hash
+        .map(|include_hash| SourceHeaderOptions { include_hash }),
     })
     .await
   }
 }
+
+struct SharedVisitor(Rc<dyn TextChangeVisitor>);
+
+impl TextChangeVisitor for SharedVisitor {
+  fn visit(&self, params: &TextChangeVisitorParams) -> Result<Vec<TextChange>> {
+    self.0.visit(params)
+  }
+}
@@ -5,6 +5,26 @@ use pretty_assertions::assert_eq;
 #[macro_use]
 mod integration;
 
+use anyhow::Result;
+use deno_node_transform::deno_ast::MediaType;
+use deno_node_transform::get_graph_dot_text;
+use deno_node_transform::get_graph_json_text;
+use deno_node_transform::DenoApiUsage;
+use deno_node_transform::DenoMemberUsage;
+use deno_node_transform::LicenseComment;
+use deno_node_transform::ModuleFormat;
+use deno_node_transform::OutputDependency;
+use deno_node_transform::OutputDependencyKind;
+use deno_node_transform::OutputFileKind;
+use deno_node_transform::OutputTypesDependency;
+use deno_node_transform::RemoteLicense;
+use deno_node_transform::ShimImportStyle;
+use deno_node_transform::SpecifierExtension;
+use deno_node_transform::TestHarness;
+use deno_node_transform::TextChange;
+use deno_node_transform::TextChangeVisitor;
+use deno_node_transform::TextChangeVisitorParams;
+use deno_node_transform::TypeDirectiveMode;
 use integration::TestBuilder;
 
 #[tokio::test]
@@ -17,7 +37,7 @@ async fn transform_standalone_file() {
     .await
     .unwrap();
 
-  assert_files!(result, &[("mod.ts", "test;")]);
+  assert_files!(result.main.files, &[("mod.ts", "test;")]);
 }
 
 #[tokio::test]
@@ -31,7 +51,7 @@ async fn transform_deno_shim() {
     .unwrap();
 
   assert_files!(
-    result,
+    result.main.files,
     &[(
       "mod.ts",
       concat!(
@@ -57,7 +77,7 @@ async fn transform_deno_shim_with_name_collision() {
     .unwrap();
 
   assert_files!(
-    result,
+    result.main.files,
     &[(
       "mod.ts",
       concat!(
@@ -80,7 +100,7 @@ async fn transform_global_this_deno() {
     .unwrap();
 
   assert_files!(
-    result,
+    result.main.files,
     &[(
       "mod.ts",
       concat!(
@@ -111,7 +131,7 @@ async fn transform_deno_collision() {
     .unwrap();
 
   assert_files!(
-    result,
+    result.main.files,
     &[(
       "mod.ts",
       concat!(
@@ -138,7 +158,7 @@ async fn transform_other_file_no_extensions() {
     .unwrap();
 
   assert_files!(
-    result,
+    result.main.files,
     &[
       ("mod.ts", "import * as other from './other';"),
       ("other.ts", "5;")
@@ -147,20 +167,20 @@ async fn transform_other_file_no_extensions() {
 }
 
 #[tokio::test]
-async fn transform_other_file_keep_extensions() {
+async fn transform_other_file_js_extension() {
   let result = TestBuilder::new()
     .with_loader(|loader| {
       loader
         .add_local_file("/mod.ts", "import * as other from './other.ts';")
         .add_local_file("/other.ts", "5;");
     })
-    .keep_extensions()
+    .specifier_extension(SpecifierExtension::Js)
     .transform()
     .await
     .unwrap();
 
   assert_files!(
-    result,
+    result.main.files,
     &[
       ("mod.ts", "import * as other from './other.js';"),
       ("other.ts", "5;")
@@ -224,7 +244,7 @@ async fn transform_remote_files() {
     .unwrap();
 
   assert_files!(
-    result,
+    result.main.files,
     &[
       ("mod.ts", "import * as other from './deps/0/mod';"),
       ("deps/0/mod.ts", "import * as myOther from './other';"),
@@ -351,7 +371,7 @@ async fn transform_typescript_types_in_headers() {
     .unwrap();
 
   assert_files!(
-    result,
+    result.main.files,
     &[
       ("mod.ts", "export * from './deps/0/mod';"),
       ("deps/0/mod.js", "function test() { return 5; }"),
@@ -360,7 +380,6 @@ async fn transform_typescript_types_in_headers() {
   );
 }
 
-#[ignore]
 #[tokio::test]
 async fn transform_typescript_types_in_deno_types() {
   let result = TestBuilder::new()
@@ -372,12 +391,11 @@ async fn transform_typescript_types_in_deno_types() {
     .transform().await.unwrap();
 
   assert_files!(
-    result,
+    result.main.files,
     &[
-      // todo: remove this deno-types comment
       (
         "mod.ts",
-        "// @deno-types='./declarations.d.ts';\nexport * from './deps/0/mod';"
+        "// @deno-types='./deps/0/mod.d.ts';\nexport * from './deps/0/mod';"
       ),
       ("deps/0/mod.js", "function test() { return 5; }"),
       ("deps/0/mod.d.ts", "declare function test(): number;"),
@@ -395,10 +413,2575 @@ async fn transform_typescript_type_references() {
     })
     .transform().await.unwrap();
 
-  assert_files!(result, &[
-    ("mod.ts", "export * from './deps/0/mod';"),
-    // todo: remove this type reference directive comment
-    ("deps/0/mod.js", "/// <reference types='./declarations.d.ts' />\nfunction test() { return 5; }"),
-    ("deps/0/mod.d.ts", "declare function test(): number;"),
-  ]);
+  assert_files!(
+    result.main.files,
+    &[
+      ("mod.ts", "export * from './deps/0/mod';"),
+      (
+        "deps/0/mod.js",
+        "/// <reference types='./mod.d.ts' />\nfunction test() { return 5; }"
+      ),
+      ("deps/0/mod.d.ts", "declare function test(): number;"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_cjs_imports_and_exports() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import * as other from './other.ts';\n",
+            "import { a, b as c } from './other.ts';\n",
+            "export { a };\n",
+            "export const value = 5;\n",
+            "export default test;\n",
+            "function test() {\n",
+            "  return other.a + c;\n",
+            "}",
+          ),
+        )
+        .add_local_file("/other.ts", "export const a = 1, b = 2;");
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "import * as other from './other';\n",
+          "import { a, b as c } from './other';\n",
+          "export { a };\n",
+          "export const value = 5;\n",
+          "export default test;\n",
+          "function test() {\n",
+          "  return other.a + c;\n",
+          "}",
+        )
+      ),
+      ("other.ts", "export const a = 1, b = 2;"),
+    ]
+  );
+  assert_files!(
+    result.main.cjs_files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "Object.defineProperty(exports, \"__esModule\", { value: true });\n",
+          "Object.defineProperty(exports, \"a\", ",
+          "{ enumerable: true, get: () => _other.a });\n",
+          "Object.defineProperty(exports, \"value\", ",
+          "{ enumerable: true, get: () => value });\n",
+          "const other = require('./other');\n",
+          "const _other = require('./other');\n",
+          "\n",
+          "const value = 5;\n",
+          "exports.default = test;\n",
+          "function test() {\n",
+          "  return other.a + _other.b;\n",
+          "}\n",
+          "export {};",
+        )
+      ),
+      (
+        "other.ts",
+        concat!(
+          "Object.defineProperty(exports, \"__esModule\", { value: true });\n",
+          "Object.defineProperty(exports, \"a\", ",
+          "{ enumerable: true, get: () => a });\n",
+          "Object.defineProperty(exports, \"b\", ",
+          "{ enumerable: true, get: () => b });\n",
+          "const a = 1, b = 2;\n",
+          "export {};",
+        )
+      ),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_cjs_type_imports() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import { Options, value as otherValue } from './other.ts';\n",
+            "import { Type } from './other.ts';\n",
+            "import type { Value } from './other.ts';\n",
+            "const options: Options = { value: otherValue };\n",
+            "let type: Type<Value>;",
+          ),
+        )
+        .add_local_file(
+          "/other.ts",
+          concat!(
+            "export interface Options { value: number; }\n",
+            "export type Type<T> = T;\n",
+            "export type Value = number;\n",
+            "export const value = 5;",
+          ),
+        );
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  let mod_file = result
+    .main
+    .cjs_files
+    .iter()
+    .find(|file| file.file_path == PathBuf::from("mod.ts"))
+    .unwrap();
+  assert_eq!(
+    mod_file.file_text,
+    concat!(
+      "import type { Options } from './other'; ",
+      "const _other = require('./other');\n",
+      "import type { Type } from './other';\n",
+      "import type { Value } from './other';\n",
+      "const options: Options = { value: _other.value };\n",
+      "let type: Type<Value>;\n",
+      "export {};",
+    )
+  );
+}
+
+#[tokio::test]
+async fn transform_cjs_deno_shim_and_dynamic_import() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.js",
+          concat!(
+            "export * from './other.js';\n",
+            "const other = import('./other.js');\n",
+            "Deno.readTextFile();",
+          ),
+        )
+        .add_local_file("/other.js", "5;");
+    })
+    .entry_point("file:///mod.js")
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.cjs_files,
+    &[
+      (
+        "mod.js",
+        concat!(
+          "Object.defineProperty(exports, \"__esModule\", { value: true });\n",
+          "const denoShim = require(\"shim-package-name\");\n",
+          "{ const m = require('./other'); for (const k in m) { ",
+          "if (k !== \"default\" && !(k in exports)) ",
+          "Object.defineProperty(exports, k, ",
+          "{ enumerable: true, get: () => m[k] }); } }\n",
+          "const other = import('./other');\n",
+          "denoShim.Deno.readTextFile();",
+        )
+      ),
+      ("other.js", "5;"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_cjs_top_level_await() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        "async function test() { await 5; }\nawait test();",
+      );
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    "Top level await is not supported when outputting CommonJS (file:///mod.ts:2:1)."
+  );
+}
+
+#[tokio::test]
+async fn transform_cjs_top_level_for_await() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        "const values = [];\nfor await (const value of values) {}",
+      );
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    "Top level await is not supported when outputting CommonJS (file:///mod.ts:2:1)."
+  );
+}
+
+#[tokio::test]
+async fn transform_dynamic_import_not_output() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        concat!(
+          "const fs = await import('node:fs');\n",
+          "const chalk = await import('chalk');",
+        ),
+      );
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[(
+      "mod.ts",
+      concat!(
+        "const fs = await import('node:fs');\n",
+        "const chalk = await import('chalk');",
+      )
+    )]
+  );
+  assert_eq!(
+    result.graph.modules[0].dependencies,
+    vec![
+      OutputDependency {
+        specifier: "node:fs".to_string(),
+        rewritten_specifier: "node:fs".to_string(),
+        resolved_specifier: Some("node:fs".to_string()),
+        kind: OutputDependencyKind::Dynamic,
+      },
+      OutputDependency {
+        specifier: "chalk".to_string(),
+        rewritten_specifier: "chalk".to_string(),
+        resolved_specifier: None,
+        kind: OutputDependencyKind::Dynamic,
+      },
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_cjs_mutated_export() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import { count, increment } from './counter.ts';\n",
+            "increment();\n",
+            "console.log({ count });",
+          ),
+        )
+        .add_local_file(
+          "/counter.ts",
+          concat!(
+            "export let count = 0;\n",
+            "export function increment() {\n",
+            "  count++;\n",
+            "}",
+          ),
+        );
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.cjs_files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "const _counter = require('./counter');\n",
+          "_counter.increment();\n",
+          "console.log({ count: _counter.count });\n",
+          "export {};",
+        )
+      ),
+      (
+        "counter.ts",
+        concat!(
+          "Object.defineProperty(exports, \"__esModule\", { value: true });\n",
+          "Object.defineProperty(exports, \"count\", ",
+          "{ enumerable: true, get: () => count });\n",
+          "Object.defineProperty(exports, \"increment\", ",
+          "{ enumerable: true, get: () => increment });\n",
+          "let count = 0;\n",
+          "function increment() {\n",
+          "  count++;\n",
+          "}\n",
+          "export {};",
+        )
+      ),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_cjs_cycle() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import { b } from './other.ts';\n",
+            "export function a() {\n",
+            "  return b();\n",
+            "}",
+          ),
+        )
+        .add_local_file(
+          "/other.ts",
+          concat!(
+            "import { a } from './mod.ts';\n",
+            "export function b() {\n",
+            "  return 1;\n",
+            "}\n",
+            "export const value = a;",
+          ),
+        );
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  // the exports are defined before the other module is required, so
+  // it's able to use them while this module is still being evaluated
+  assert_files!(
+    result.main.cjs_files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "Object.defineProperty(exports, \"__esModule\", { value: true });\n",
+          "Object.defineProperty(exports, \"a\", ",
+          "{ enumerable: true, get: () => a });\n",
+          "const _other = require('./other');\n",
+          "function a() {\n",
+          "  return _other.b();\n",
+          "}\n",
+          "export {};",
+        )
+      ),
+      (
+        "other.ts",
+        concat!(
+          "Object.defineProperty(exports, \"__esModule\", { value: true });\n",
+          "Object.defineProperty(exports, \"b\", ",
+          "{ enumerable: true, get: () => b });\n",
+          "Object.defineProperty(exports, \"value\", ",
+          "{ enumerable: true, get: () => value });\n",
+          "const _mod = require('./mod');\n",
+          "function b() {\n",
+          "  return 1;\n",
+          "}\n",
+          "const value = _mod.a;\n",
+          "export {};",
+        )
+      ),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_test_modules_node_test() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "export function add(a: number, b: number) { return a + b; }")
+        .add_local_file(
+          "/mod.test.ts",
+          concat!(
+            "import { assertEquals } from 'https://deno.land/std@0.109.0/testing/asserts.ts';\n",
+            "import { add } from './mod.ts';\n",
+            "Deno.test('add', () => { assertEquals(add(1, 2), 3); });\n",
+            "Deno.test({ name: 'ignored', fn() {}, ignore: true });\n",
+            "Deno.test(function namedTest() {});",
+          ),
+        );
+    })
+    .add_test_entry_point("file:///mod.test.ts")
+    .assertion_module("assertion-module")
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[(
+      "mod.ts",
+      "export function add(a: number, b: number) { return a + b; }"
+    )]
+  );
+  assert_files!(
+    result.test.files,
+    &[(
+      "mod.test.ts",
+      concat!(
+        "import { test } from \"node:test\";\n",
+        "import { assertEquals } from 'assertion-module';\n",
+        "import { add } from './mod';\n",
+        "test('add', () => { assertEquals(add(1, 2), 3); });\n",
+        "((t: any) => test(t.name, { skip: t.ignore, only: t.only }, t.fn))",
+        "({ name: 'ignored', fn() {}, ignore: true });\n",
+        "((fn: any) => test(fn.name, fn))(function namedTest() {});",
+      )
+    )]
+  );
+}
+
+#[tokio::test]
+async fn transform_test_modules_custom_harness() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "export const value = 5;")
+        .add_local_file(
+          "/mod.test.js",
+          concat!(
+            "import { value } from './mod.ts';\n",
+            "const it = 5;\n",
+            "Deno.test('value', () => {});\n",
+            "Deno.readTextFile();",
+          ),
+        );
+    })
+    .add_test_entry_point("file:///mod.test.js")
+    .test_harness(TestHarness::Custom {
+      module: "test-harness".to_string(),
+      export_name: "it".to_string(),
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.test.files,
+    &[(
+      "mod.test.js",
+      concat!(
+        "import * as denoShim from \"shim-package-name\";\n",
+        "import { it as it1 } from \"test-harness\";\n",
+        "import { value } from './mod';\n",
+        "const it = 5;\n",
+        "it1('value', () => {});\n",
+        "denoShim.Deno.readTextFile();",
+      )
+    )]
+  );
+  assert_files!(
+    result.test.cjs_files,
+    &[(
+      "mod.test.js",
+      concat!(
+        "const denoShim = require(\"shim-package-name\");\n",
+        "const { it: it1 } = require(\"test-harness\");\n",
+        "require('./mod');\n",
+        "const it = 5;\n",
+        "it1('value', () => {});\n",
+        "denoShim.Deno.readTextFile();",
+      )
+    )]
+  );
+}
+
+#[tokio::test]
+async fn transform_bundle() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          "import { add } from './math.ts';\nexport const value: number = add(1, 2);\nDeno.cwd();",
+        )
+        .add_local_file(
+          "/math.ts",
+          "export function add(a: number, b: number) { return a + b; }",
+        );
+    })
+    .bundle(false)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_eq!(result.main.files.len(), 1);
+  let file = &result.main.files[0];
+  assert_eq!(file.file_path, PathBuf::from("mod.js"));
+  assert!(file
+    .file_text
+    .starts_with("import * as denoShim from \"shim-package-name\";"));
+  assert!(file.file_text.contains("function add(a, b)"));
+  assert!(!file.file_text.contains("./math"));
+}
+
+#[tokio::test]
+async fn transform_bundle_declaration_files() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import { add } from './math.utils.ts';\n",
+            "// @deno-types=\"./other.d.ts\"\n",
+            "import { value } from './other.js';\n",
+            "export const sum: number = add(value, 2);",
+          ),
+        )
+        .add_local_file(
+          "/math.utils.ts",
+          "export function add(a: number, b: number) { return a + b; }",
+        )
+        .add_local_file("/other.js", "export const value = 1;")
+        .add_local_file("/other.d.ts", "export const value: number;");
+    })
+    .bundle(false)
+    .transform()
+    .await
+    .unwrap();
+
+  let file_paths = result
+    .main
+    .files
+    .iter()
+    .map(|file| file.file_path.clone())
+    .collect::<Vec<_>>();
+  assert_eq!(
+    file_paths,
+    vec![PathBuf::from("mod.js"), PathBuf::from("other.d.ts")]
+  );
+  assert_eq!(
+    result.main.files[1].file_text,
+    "export const value: number;"
+  );
+  let file_text = &result.main.files[0].file_text;
+  assert!(file_text.contains("function add(a, b)"));
+  assert!(file_text.contains("const value = 1;"));
+  assert!(!file_text.contains("./math"));
+  assert!(!file_text.contains("./other"));
+}
+
+#[tokio::test]
+async fn transform_bundle_file_path_collision() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "import './a.ts';\nimport './a.js';")
+        .add_local_file("/a.ts", "export const a = 1;")
+        .add_local_file("/a.js", "export const b = 2;");
+    })
+    .bundle(false)
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    "Could not bundle a.js and a.ts because both are imported as a."
+  );
+}
+
+#[tokio::test]
+async fn transform_bundle_dynamic_import() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "const other = import('./other.ts');")
+        .add_local_file("/other.ts", "export const value = 5;");
+    })
+    .bundle(false)
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    "Dynamic imports are not supported when bundling (import(\"./other.ts\") in file:///mod.ts)."
+  );
+}
+
+#[tokio::test]
+async fn transform_bundle_bare_specifier() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "import { test } from 'other';\ntest();");
+    })
+    .bundle(false)
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    "Could not bundle the bare specifier \"other\" in mod.ts."
+  );
+}
+
+#[tokio::test]
+async fn transform_bundle_dual_module_format() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file("/mod.ts", "export const value = 5;");
+    })
+    .module_format(ModuleFormat::Dual)
+    .bundle(true)
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    "Bundling is not supported when outputting CommonJS."
+  );
+}
+
+#[tokio::test]
+async fn transform_typescript_type_references_non_declaration() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file("/mod.ts", "export * from 'http://localhost/mod.js';")
+      .add_remote_file("http://localhost/mod.js", "/// <reference types='./declarations.ts' />\nfunction test() { return 5; }")
+      .add_remote_file("http://localhost/declarations.ts", "export function test(): number { return 5; }");
+    })
+    .transform().await.err().unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    concat!(
+      "Only .d.ts files are supported as the types of http://localhost/mod.js, ",
+      "but found a .ts file for: http://localhost/declarations.ts",
+    )
+  );
+}
+
+#[tokio::test]
+async fn transform_typescript_deno_types_non_declaration() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          "// @deno-types='./types.ts';\nimport { test } from 'http://localhost/mod.js';",
+        )
+        .add_remote_file("http://localhost/mod.js", "export const test = 5;")
+        .add_local_file("/types.ts", "export const test: number = 5;");
+    })
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    concat!(
+      "Only .d.ts files are supported as the types of http://localhost/mod.js, ",
+      "but found a .ts file for: file:///types.ts",
+    )
+  );
+}
+
+#[tokio::test]
+async fn transform_typescript_conflicting_deno_types() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file("/mod.ts", "// @deno-types='./a.d.ts';\nimport { test } from 'http://localhost/mod.js';\nexport * from './other.ts';")
+      .add_local_file("/other.ts", "// @deno-types='./b.d.ts';\nexport { test } from 'http://localhost/mod.js';")
+      .add_remote_file("http://localhost/mod.js", "export function test() { return 5; }")
+      .add_local_file("/a.d.ts", "export declare function test(): number;")
+      .add_local_file("/b.d.ts", "export declare function test(): number;");
+    })
+    .transform().await.err().unwrap().to_string();
+
+  assert!(err_message.starts_with(
+    "Found conflicting declaration files for http://localhost/mod.js:\n"
+  ));
+  assert!(
+    err_message.contains("\n  file:///a.d.ts (referenced at file:///mod.ts:1:")
+  );
+  assert!(err_message
+    .contains("\n  file:///b.d.ts (referenced at file:///other.ts:1:"));
+  assert!(err_message.ends_with(
+    "\nSpecify which declaration file to use via the types overrides."
+  ));
+}
+
+#[tokio::test]
+async fn transform_typescript_conflicting_deno_types_override() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file("/mod.ts", "// @deno-types='./a.d.ts';\nimport { test } from 'http://localhost/mod.js';\nexport * from './other.ts';")
+      .add_local_file("/other.ts", "// @deno-types='./b.d.ts';\nexport { test } from 'http://localhost/mod.js';")
+      .add_remote_file("http://localhost/mod.js", "export function test() { return 5; }")
+      .add_local_file("/a.d.ts", "export declare function test(): number;")
+      .add_local_file("/b.d.ts", "export declare function test(): string;");
+    })
+    .add_types_override("http://localhost/mod.js", "file:///b.d.ts")
+    .transform().await.unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        "import { test } from './deps/0/mod';\nexport * from './other';"
+      ),
+      (
+        "other.ts",
+        "// @deno-types='./deps/0/mod.d.ts';\nexport { test } from './deps/0/mod';"
+      ),
+      ("deps/0/mod.js", "export function test() { return 5; }"),
+      ("deps/0/mod.d.ts", "export declare function test(): string;"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_typescript_type_directives_remove() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "/// <reference lib='deno.ns' />\n",
+            "/// <reference types='node' />\n",
+            "/// <reference path='./globals.d.ts' />\n",
+            "// @deno-types='./declarations.d.ts';\n",
+            "export * from 'http://localhost/mod.js';",
+          ),
+        )
+        .add_local_file("/globals.d.ts", "declare var value: number;")
+        .add_remote_file(
+          "http://localhost/mod.js",
+          "function test() { return 5; }",
+        )
+        .add_local_file(
+          "/declarations.d.ts",
+          "declare function test(): number;",
+        );
+    })
+    .type_directives(TypeDirectiveMode::Remove)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        "/// <reference types='node' />\nexport * from './deps/0/mod';"
+      ),
+      ("globals.d.ts", "declare var value: number;"),
+      ("deps/0/mod.js", "function test() { return 5; }"),
+      ("deps/0/mod.d.ts", "declare function test(): number;"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_typescript_type_directives_rewrite() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "/// <reference lib='deno.unstable' />\n",
+            "/// <reference path='./globals.d.ts' />\n",
+            "export * from 'http://localhost/mod.ts';",
+          ),
+        )
+        .add_local_file("/globals.d.ts", "declare var value: number;")
+        .add_remote_file(
+          "http://localhost/mod.ts",
+          "/// <reference lib='deno.ns' />\nexport const test = 5;",
+        );
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        "/// <reference path='./globals.d.ts' />\nexport * from './deps/0/mod';"
+      ),
+      ("globals.d.ts", "declare var value: number;"),
+      ("deps/0/mod.ts", "export const test = 5;"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_custom_visitor() {
+  use deno_node_transform::deno_ast::view::*;
+
+  struct CustomVisitor;
+
+  impl TextChangeVisitor for CustomVisitor {
+    fn visit(
+      &self,
+      params: &TextChangeVisitorParams,
+    ) -> Result<Vec<TextChange>> {
+      let mut text_changes = Vec::new();
+      for child in params.program.children() {
+        match child {
+          Node::ExprStmt(expr_stmt) if is_deno_bench_call(&expr_stmt.expr) => {
+            text_changes.push(TextChange {
+              span: expr_stmt.span(),
+              new_text: String::new(),
+            });
+          }
+          Node::ExprStmt(expr_stmt) => {
+            if let Expr::Call(call_expr) = &expr_stmt.expr {
+              if let ExprOrSuper::Expr(Expr::Ident(ident)) = &call_expr.callee {
+                if &**ident.sym() == "log" {
+                  text_changes.push(TextChange {
+                    span: ident.span(),
+                    new_text: "console.log".to_string(),
+                  });
+                }
+              }
+            }
+          }
+          _ => {}
+        }
+      }
+      Ok(text_changes)
+    }
+  }
+
+  fn is_deno_bench_call(expr: &Expr) -> bool {
+    let member_expr = match expr {
+      Expr::Call(call_expr) => match &call_expr.callee {
+        ExprOrSuper::Expr(Expr::Member(member_expr)) => member_expr,
+        _ => return false,
+      },
+      _ => return false,
+    };
+    match (&member_expr.obj, &member_expr.prop) {
+      (ExprOrSuper::Expr(Expr::Ident(obj)), Expr::Ident(prop)) => {
+        &**obj.sym() == "Deno" && &**prop.sym() == "bench"
+      }
+      _ => false,
+    }
+  }
+
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          "import { log } from './logger.ts';\nDeno.bench('a', () => {});\nlog('hi');",
+        )
+        .add_local_file("/logger.ts", "export function log(message: string) {}");
+    })
+    .add_visitor(CustomVisitor)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "import { log } from './logger.js';\n",
+          "\n",
+          "console.log('hi');",
+        )
+      ),
+      ("logger.ts", "export function log(message: string) {}"),
+    ]
+  );
+  // the removed `Deno.bench` call is not shimmed
+  assert_eq!(result.deno_api_usage, Vec::new());
+}
+
+#[tokio::test]
+async fn transform_custom_visitor_overlapping_text_change() {
+  use deno_node_transform::deno_ast::view::*;
+
+  struct CustomVisitor;
+
+  impl TextChangeVisitor for CustomVisitor {
+    fn visit(
+      &self,
+      params: &TextChangeVisitorParams,
+    ) -> Result<Vec<TextChange>> {
+      let mut text_changes = Vec::new();
+      for child in params.program.children() {
+        if let Node::ImportDecl(import_decl) = child {
+          text_changes.push(TextChange {
+            span: import_decl.span(),
+            new_text: "import { log } from \"internal-logger\";".to_string(),
+          });
+        }
+      }
+      Ok(text_changes)
+    }
+  }
+
+  let err = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "import { log } from './logger.ts';")
+        .add_local_file("/logger.ts", "export function log() {}");
+    })
+    .add_visitor(CustomVisitor)
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  // the import declaration overlaps the module specifier's text change
+  assert_eq!(
+    format!("{:#}", err),
+    concat!(
+      "Error transforming file:///mod.ts: ",
+      "Text change 21-32 overlaps with text change 0-34.",
+    ),
+  );
+}
+
+#[tokio::test]
+async fn transform_typescript_type_directives_ignore_next_line() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        concat!(
+          "// dnt-ignore-next-line\n",
+          "/// <reference lib='deno.ns' />\n",
+          "/// <reference lib='deno.unstable' />\n",
+          "export const value = 5;",
+        ),
+      );
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[(
+      "mod.ts",
+      concat!(
+        "// dnt-ignore-next-line\n",
+        "/// <reference lib='deno.ns' />\n",
+        "export const value = 5;",
+      )
+    )]
+  );
+}
+
+#[tokio::test]
+async fn transform_cjs_ignore_next_line() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "// dnt-ignore-next-line\n",
+            "export * from './other.ts';\n",
+            "export const value = 5;",
+          ),
+        )
+        .add_local_file("/other.ts", "");
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.cjs_files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "// dnt-ignore-next-line\n",
+          "export * from './other.ts';\n",
+          "Object.defineProperty(exports, \"__esModule\", { value: true });\n",
+          "Object.defineProperty(exports, \"value\", ",
+          "{ enumerable: true, get: () => value });\n",
+          "const value = 5;\n",
+          "export {};",
+        )
+      ),
+      ("other.ts", "\nexport {};"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_cjs_ignore_file() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        "// dnt-ignore-file\nexport const value = await 5;",
+      );
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.cjs_files,
+    &[(
+      "mod.ts",
+      "// dnt-ignore-file\nexport const value = await 5;"
+    )]
+  );
+}
+
+#[tokio::test]
+async fn transform_ignore_next_line() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "// dnt-ignore-next-line\n",
+            "import { test } from 'http://localhost/mod.ts';\n",
+            "export * from 'http://localhost/other.ts';\n",
+            "// dnt-ignore-next-line\n",
+            "if (typeof Deno !== 'undefined') {}\n",
+            "Deno.readTextFile();",
+          ),
+        )
+        .add_remote_file("http://localhost/mod.ts", "export const test = 5;")
+        .add_remote_file(
+          "http://localhost/other.ts",
+          "export const other = 5;",
+        );
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "import * as denoShim from \"shim-package-name\";\n",
+          "// dnt-ignore-next-line\n",
+          "import { test } from 'http://localhost/mod.ts';\n",
+          "export * from './deps/0/other';\n",
+          "// dnt-ignore-next-line\n",
+          "if (typeof Deno !== 'undefined') {}\n",
+          "denoShim.Deno.readTextFile();",
+        )
+      ),
+      ("deps/0/mod.ts", "export const test = 5;"),
+      ("deps/0/other.ts", "export const other = 5;"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_ignore_file() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "// dnt-ignore-file\n",
+            "import 'http://localhost/mod.ts';\n",
+            "Deno.readTextFile();",
+          ),
+        )
+        .add_remote_file("http://localhost/mod.ts", "Deno.readTextFile();");
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "// dnt-ignore-file\n",
+          "import 'http://localhost/mod.ts';\n",
+          "Deno.readTextFile();",
+        )
+      ),
+      (
+        "deps/0/mod.ts",
+        "import * as denoShim from \"shim-package-name\";\ndenoShim.Deno.readTextFile();"
+      ),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_externals() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import { sdk } from 'http://localhost/sdk/mod.ts';\n",
+            "import { value } from './generated.ts';\n",
+            "export * from './other.ts';",
+          ),
+        )
+        .add_local_file("/other.ts", "export const other = 5;");
+    })
+    .add_external("http://localhost/sdk/", Some("sdk/"))
+    .add_external("file:///generated.ts", None)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "import { sdk } from 'sdk/mod.ts';\n",
+          "import { value } from './generated.ts';\n",
+          "export * from './other';",
+        )
+      ),
+      ("other.ts", "export const other = 5;"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_externals_path_boundary() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import 'http://localhost/sdk/mod.ts';\n",
+            "import 'http://localhost/sdk2/mod.ts';",
+          ),
+        )
+        .add_remote_file("http://localhost/sdk2/mod.ts", "");
+    })
+    .add_external("http://localhost/sdk", Some("sdk"))
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      ("mod.ts", "import 'sdk/mod.ts';\nimport './deps/0/mod';"),
+      ("deps/0/mod.ts", ""),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_defines() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        concat!(
+          "if (Deno.build.os === 'windows') {}\n",
+          "console.log(Deno.version.deno);\n",
+          "Deno.readTextFile();",
+        ),
+      );
+    })
+    .add_define(
+      "Deno.build.os",
+      "process.platform === \"win32\" ? \"windows\" : process.platform",
+    )
+    .add_define("Deno.version", "{ deno: \"1.0.0\" }")
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[(
+      "mod.ts",
+      concat!(
+        "import * as denoShim from \"shim-package-name\";\n",
+        "if ((process.platform === \"win32\" ? \"windows\" : process.platform) === 'windows') {}\n",
+        "console.log(({ deno: \"1.0.0\" }).deno);\n",
+        "denoShim.Deno.readTextFile();",
+      )
+    )]
+  );
+}
+
+#[tokio::test]
+async fn transform_defines_no_shim() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file("/mod.ts", "const os = Deno.build.os;");
+    })
+    .add_define("Deno.build.os", "process.platform")
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[("mod.ts", "const os = process.platform;")]
+  );
+}
+
+#[tokio::test]
+async fn transform_defines_assignment_targets() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        concat!(
+          "Deno.env.MODE = 1;\n",
+          "Deno.version.count++;\n",
+          "const mode = Deno.env.MODE;\n",
+          "const value = Deno.version.value;",
+        ),
+      );
+    })
+    .add_define("Deno.env.MODE", "\"prod\"")
+    .add_define("Deno.version.count", "5")
+    .add_define("Deno.version.value", "1.5")
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[(
+      "mod.ts",
+      concat!(
+        "import * as denoShim from \"shim-package-name\";\n",
+        "denoShim.Deno.env.MODE = 1;\n",
+        "denoShim.Deno.version.count++;\n",
+        "const mode = \"prod\";\n",
+        "const value = 1.5;",
+      )
+    )]
+  );
+}
+
+#[tokio::test]
+async fn transform_defines_non_simple_replacement() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file("/mod.ts", "const value = Deno.value;");
+    })
+    .add_define("Deno.value", "...")
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(result.main.files, &[("mod.ts", "const value = (...);")]);
+}
+
+#[tokio::test]
+async fn transform_deno_api_usage() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          "import './other.ts';\nDeno.readTextFile();\nconst os = Deno.build.os;",
+        )
+        .add_local_file("/other.ts", "export const value = 5;");
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_eq!(
+    result.deno_api_usage,
+    vec![DenoApiUsage {
+      specifier: "file:///mod.ts".to_string(),
+      file_path: PathBuf::from("mod.ts"),
+      members: vec![
+        DenoMemberUsage {
+          path: "Deno.readTextFile".to_string(),
+          line: 2,
+          column: 1,
+        },
+        DenoMemberUsage {
+          path: "Deno.build.os".to_string(),
+          line: 3,
+          column: 12,
+        },
+      ],
+    }]
+  );
+}
+
+#[tokio::test]
+async fn transform_unsupported_deno_apis() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        "Deno.readTextFile();\nDeno.build.os;\nDeno.run({ cmd: [] });",
+      );
+    })
+    .supported_deno_apis(&["Deno.readTextFile", "Deno.build"])
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    "Found unsupported Deno APIs:\n  Deno.run (file:///mod.ts:3:1)"
+  );
+}
+
+#[tokio::test]
+async fn transform_local_shim() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          "import './sub/other.ts';\nDeno.readTextFile('a');",
+        )
+        .add_local_file("/sub/other.ts", "Deno.cwd();\nDeno.run();");
+    })
+    .local_shim()
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "import * as denoShim from \"./_dnt.shims\";\n",
+          "import './sub/other';\n",
+          "denoShim.Deno.readTextFile('a');",
+        )
+      ),
+      (
+        "sub/other.ts",
+        concat!(
+          "import * as denoShim from \"../_dnt.shims\";\n",
+          "denoShim.Deno.cwd();\n",
+          "denoShim.Deno.run();",
+        )
+      ),
+      (
+        "_dnt.shims.ts",
+        concat!(
+          "import * as fs from \"fs\";\n",
+          "\n",
+          "export const Deno: any = {\n",
+          "  cwd: () => process.cwd(),\n",
+          "  readTextFile: (path: string | URL) => fs.promises.readFile(path, \"utf8\"),\n",
+          "  get run(): any {\n",
+          "    throw new Error(\"Deno.run is not implemented by the local shim.\");\n",
+          "  },\n",
+          "};\n",
+        )
+      ),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_local_shim_not_used() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file("/mod.ts", "export const value = 5;");
+    })
+    .local_shim()
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(result.main.files, &[("mod.ts", "export const value = 5;")]);
+}
+
+#[tokio::test]
+async fn transform_local_shim_types() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        concat!(
+          "export function read(reader: Deno.Reader): Deno.errors.NotFound {}\n",
+          "type Errors = Deno.errors.BadResource | Deno.errors.NotFound;\n",
+          "type Cwd = typeof Deno.cwd;\n",
+          "Deno.cwd();",
+        ),
+      );
+    })
+    .local_shim()
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  let expected_types = concat!(
+    "export namespace Deno {\n",
+    "  export type Reader = any;\n",
+    "  export namespace errors {\n",
+    "    export type BadResource = any;\n",
+    "    export type NotFound = any;\n",
+    "  }\n",
+    "}\n",
+  );
+  assert_eq!(
+    result.main.files[1].file_path,
+    PathBuf::from("_dnt.shims.ts")
+  );
+  assert_eq!(
+    result.main.files[1].file_text,
+    format!(
+      concat!(
+        "export const Deno: any = {{\n",
+        "  cwd: () => process.cwd(),\n",
+        "}};\n",
+        "{}",
+      ),
+      expected_types,
+    ),
+  );
+  assert_eq!(
+    result.main.cjs_files[1].file_text,
+    format!(
+      concat!(
+        "module.exports.Deno = {{\n",
+        "  cwd: () => process.cwd(),\n",
+        "}};\n",
+        "{}",
+        "export {{}};\n",
+      ),
+      expected_types,
+    ),
+  );
+}
+
+#[tokio::test]
+async fn transform_local_shim_file_path_collision() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "import './_dnt.shims.ts';\nDeno.cwd();")
+        .add_local_file("/_dnt.shims.ts", "export {};");
+    })
+    .local_shim()
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    concat!(
+      "Could not output the local shim because file:///_dnt.shims.ts ",
+      "is output at _dnt.shims.ts.",
+    ),
+  );
+}
+
+#[tokio::test]
+async fn transform_named_shim_import() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        "const denoShimDeno = 1;\nDeno.readTextFile();\nglobalThis.setTimeout;",
+      );
+    })
+    .shim_import_style(ShimImportStyle::Named)
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[(
+      "mod.ts",
+      concat!(
+        "import { Deno as denoShimDeno1 } from \"shim-package-name\";\n",
+        "const denoShimDeno = 1;\n",
+        "denoShimDeno1.readTextFile();\n",
+        "({ Deno: denoShimDeno1, ...globalThis }).setTimeout;",
+      )
+    )]
+  );
+  assert_files!(
+    result.main.cjs_files,
+    &[(
+      "mod.ts",
+      concat!(
+        "const { Deno: denoShimDeno1 } = require(\"shim-package-name\");\n",
+        "const denoShimDeno = 1;\n",
+        "denoShimDeno1.readTextFile();\n",
+        "({ Deno: denoShimDeno1, ...globalThis }).setTimeout;\n",
+        "export {};",
+      )
+    )]
+  );
+}
+
+#[tokio::test]
+async fn transform_deno_type_references() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import \"./other.ts\";\n",
+            "export function read(reader: Deno.Reader): Deno.FsFile {}\n",
+            "export class Closer implements Deno.Closer {}\n",
+            "type ReadFile = typeof Deno.readFile;",
+          ),
+        )
+        .add_local_file(
+          "/other.ts",
+          "const file: Deno.FsFile = Deno.openSync(\"file.txt\");",
+        );
+    })
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "import type * as denoShim from \"shim-package-name\";\n",
+          "import \"./other\";\n",
+          "export function read(reader: denoShim.Deno.Reader): denoShim.Deno.FsFile {}\n",
+          "export class Closer implements denoShim.Deno.Closer {}\n",
+          "type ReadFile = typeof denoShim.Deno.readFile;",
+        )
+      ),
+      (
+        "other.ts",
+        concat!(
+          "import * as denoShim from \"shim-package-name\";\n",
+          "const file: denoShim.Deno.FsFile = denoShim.Deno.openSync(\"file.txt\");",
+        )
+      )
+    ]
+  );
+  // the type-only import is elided by the compiler, so it's kept for cjs
+  assert!(result.main.cjs_files[0].file_text.starts_with(concat!(
+    "Object.defineProperty(exports, \"__esModule\", { value: true });\n",
+    "Object.defineProperty(exports, \"read\", ",
+    "{ enumerable: true, get: () => read });\n",
+    "Object.defineProperty(exports, \"Closer\", ",
+    "{ enumerable: true, get: () => Closer });\n",
+    "import type * as denoShim from \"shim-package-name\";\n",
+  )));
+  assert_eq!(
+    result
+      .deno_api_usage
+      .iter()
+      .map(|usage| usage.specifier.as_str())
+      .collect::<Vec<_>>(),
+    vec!["file:///other.ts"],
+  );
+}
+
+#[tokio::test]
+async fn transform_import_types() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "type Foo = import('http://localhost/mod.ts').Foo;\n",
+            "namespace Other {\n",
+            "  export type Bar = typeof import('./bar.ts');\n",
+            "}",
+          ),
+        )
+        .add_remote_file("http://localhost/mod.ts", "export interface Foo {}")
+        .add_local_file("/bar.ts", "export const bar = 5;");
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "type Foo = import('./deps/0/mod').Foo;\n",
+          "namespace Other {\n",
+          "  export type Bar = typeof import('./bar');\n",
+          "}",
+        )
+      ),
+      ("bar.ts", "export const bar = 5;"),
+      ("deps/0/mod.ts", "export interface Foo {}"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_import_type_not_output() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        concat!(
+          "type Stats = import('node:fs').Stats;\n",
+          "type Request = import('express').Request;",
+        ),
+      );
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[(
+      "mod.ts",
+      concat!(
+        "type Stats = import('node:fs').Stats;\n",
+        "type Request = import('express').Request;",
+      )
+    )]
+  );
+}
+
+#[tokio::test]
+async fn transform_jsdoc_import_types() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.js",
+          concat!(
+            "/** @type {import('./types.ts').Options} */\n",
+            "export const options = {};\n",
+            "/** @typedef {import(\"http://localhost/mod.ts\").Foo} Foo */\n",
+            "// not jsdoc: import('./other.ts')",
+          ),
+        )
+        .add_local_file("/types.ts", "export interface Options {}")
+        .add_remote_file("http://localhost/mod.ts", "export interface Foo {}");
+    })
+    .entry_point("file:///mod.js")
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.js",
+        concat!(
+          "/** @type {import('./types').Options} */\n",
+          "export const options = {};\n",
+          "/** @typedef {import(\"./deps/0/mod\").Foo} Foo */\n",
+          "// not jsdoc: import('./other.ts')",
+        )
+      ),
+      ("types.ts", "export interface Options {}"),
+      ("deps/0/mod.ts", "export interface Foo {}"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_jsdoc_import_types_not_output() {
+  let file_text = concat!(
+    "/** @type {import('express').Request} */\n",
+    "export let request;\n",
+    "/** @type {import(\"node:fs\").Stats} */\n",
+    "export let stats;\n",
+    "/**\n",
+    " * Gets the options.\n",
+    " * @example\n",
+    " * /** @type {import('./example.ts').Options} *\\/\n",
+    " * const options = getOptions();\n",
+    " * @returns {import('./types.ts').Options}\n",
+    " */\n",
+    "export function getOptions() {}",
+  );
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.js", file_text)
+        .add_local_file("/types.ts", "export interface Options {}");
+    })
+    .entry_point("file:///mod.js")
+    .transform()
+    .await
+    .unwrap();
+
+  let expected_text = file_text.replace("'./types.ts'", "'./types'");
+  assert_files!(
+    result.main.files,
+    &[
+      ("mod.js", expected_text.as_str()),
+      ("types.ts", "export interface Options {}"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_import_equals() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import fs = require('./fs.ts');\n",
+            "export import other = require('http://localhost/other.ts');",
+          ),
+        )
+        .add_local_file("/fs.ts", "export const readFile = 5;")
+        .add_remote_file(
+          "http://localhost/other.ts",
+          "export const value = 5;",
+        );
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "import fs = require('./fs');\n",
+          "export import other = require('./deps/0/other');",
+        )
+      ),
+      ("fs.ts", "export const readFile = 5;"),
+      ("deps/0/other.ts", "export const value = 5;"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_import_equals_entity_name() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader.add_local_file(
+        "/mod.ts",
+        concat!(
+          "namespace Outer {\n",
+          "  export const value = 5;\n",
+          "}\n",
+          "export import value = Outer.value;",
+        ),
+      );
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[(
+      "mod.ts",
+      concat!(
+        "namespace Outer {\n",
+        "  export const value = 5;\n",
+        "}\n",
+        "export import value = Outer.value;",
+      )
+    )]
+  );
+}
+
+#[tokio::test]
+async fn transform_specifier_extensions() {
+  async fn transform(extension: SpecifierExtension) -> Vec<String> {
+    let result = TestBuilder::new()
+      .with_loader(|loader| {
+        loader
+          .add_local_file(
+            "/mod.ts",
+            concat!(
+              "import './a.tsx';\n",
+              "import './b.jsx';\n",
+              "import './c.mts';\n",
+              "import './d.cts';\n",
+              "import './e.d.ts';\n",
+              "import './f.test.ts';",
+            ),
+          )
+          .add_local_file("/a.tsx", "")
+          .add_local_file("/b.jsx", "")
+          .add_local_file("/c.mts", "")
+          .add_local_file("/d.cts", "")
+          .add_local_file("/e.d.ts", "")
+          .add_local_file("/f.test.ts", "");
+      })
+      .specifier_extension(extension)
+      .module_format(ModuleFormat::Dual)
+      .transform()
+      .await
+      .unwrap();
+    let get_mod_text = |files: &[deno_node_transform::OutputFile]| {
+      files
+        .iter()
+        .find(|f| f.file_path == PathBuf::from("mod.ts"))
+        .unwrap()
+        .file_text
+        .clone()
+    };
+    vec![
+      get_mod_text(&result.main.files),
+      get_mod_text(&result.main.cjs_files),
+    ]
+  }
+
+  assert_eq!(
+    transform(SpecifierExtension::None).await[0],
+    concat!(
+      "import './a';\n",
+      "import './b';\n",
+      "import './c';\n",
+      "import './d';\n",
+      "import './e';\n",
+      "import './f.test';",
+    )
+  );
+  assert_eq!(
+    transform(SpecifierExtension::Js).await[0],
+    concat!(
+      "import './a.js';\n",
+      "import './b.js';\n",
+      "import './c.mjs';\n",
+      "import './d.cjs';\n",
+      "import './e.js';\n",
+      "import './f.test.js';",
+    )
+  );
+  assert_eq!(
+    transform(SpecifierExtension::Original).await[0],
+    concat!(
+      "import './a.tsx';\n",
+      "import './b.jsx';\n",
+      "import './c.mts';\n",
+      "import './d.cts';\n",
+      "import './e.d.ts';\n",
+      "import './f.test.ts';",
+    )
+  );
+}
+
+#[tokio::test]
+async fn transform_mjs_specifier_extension() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "/// <reference types=\"./d.d.ts\" />\n",
+            "import './a.ts';\n",
+            "import './b.js';\n",
+            "import './c.cts';",
+          ),
+        )
+        .add_local_file("/a.ts", "")
+        .add_local_file("/b.js", "")
+        .add_local_file("/c.cts", "")
+        .add_local_file("/d.d.ts", "");
+    })
+    .specifier_extension(SpecifierExtension::Mjs)
+    .module_format(ModuleFormat::Dual)
+    .transform()
+    .await
+    .unwrap();
+
+  // the output files need extensions matching the specifiers
+  let get_file_paths = |files: &[deno_node_transform::OutputFile]| {
+    let mut file_paths = files
+      .iter()
+      .map(|f| f.file_path.to_string_lossy().to_string())
+      .collect::<Vec<_>>();
+    file_paths.sort();
+    file_paths
+  };
+  assert_eq!(
+    get_file_paths(&result.main.files),
+    vec!["a.mts", "b.mjs", "c.mts", "d.d.mts", "mod.mts"],
+  );
+  assert_eq!(
+    get_file_paths(&result.main.cjs_files),
+    vec!["a.cts", "b.cjs", "c.cts", "d.d.cts", "mod.cts"],
+  );
+  assert_eq!(
+    result.graph.mappings.get("file:///mod.ts"),
+    Some(&PathBuf::from("mod.mts")),
+  );
+
+  let mod_file = result
+    .main
+    .files
+    .iter()
+    .find(|f| f.file_path == PathBuf::from("mod.mts"))
+    .unwrap();
+  assert_eq!(
+    mod_file.file_text,
+    concat!(
+      "/// <reference types=\"./d.d.mts\" />\n",
+      "import './a.mjs';\n",
+      "import './b.mjs';\n",
+      "import './c.mjs';",
+    ),
+  );
+  let cjs_mod_file = result
+    .main
+    .cjs_files
+    .iter()
+    .find(|f| f.file_path == PathBuf::from("mod.cts"))
+    .unwrap();
+  assert!(cjs_mod_file
+    .file_text
+    .contains("/// <reference types=\"./d.d.cts\" />\n"));
+  assert!(cjs_mod_file.file_text.contains("require('./a.cjs');\n"));
+  assert!(cjs_mod_file.file_text.contains("require('./c.cjs');"));
+}
+
+#[tokio::test]
+async fn transform_mjs_specifier_extension_jsx() {
+  let err_message = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "import './a.tsx';")
+        .add_local_file("/a.tsx", "");
+    })
+    .specifier_extension(SpecifierExtension::Mjs)
+    .transform()
+    .await
+    .err()
+    .unwrap();
+
+  assert_eq!(
+    err_message.to_string(),
+    concat!(
+      "Could not output a.tsx because only TypeScript, JavaScript and ",
+      "declaration files can use the Mjs specifier extension.",
+    ),
+  );
+}
+
+#[tokio::test]
+async fn transform_remote_declaration_file() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "import 'http://localhost/mod.js';")
+        .add_remote_file_with_headers(
+          "http://localhost/mod.js",
+          "export const value = 5;",
+          &[("x-typescript-types", "./mod.d.ts")],
+        )
+        .add_remote_file(
+          "http://localhost/mod.d.ts",
+          "export declare const value: number;",
+        );
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      ("mod.ts", "import './deps/0/mod';"),
+      ("deps/0/mod.js", "export const value = 5;"),
+      ("deps/0/mod.d.ts", "export declare const value: number;"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_output_file_kinds() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          "import 'http://localhost/mod.js';\nimport './types.d.ts';",
+        )
+        .add_local_file("/types.d.ts", "")
+        .add_remote_file_with_headers(
+          "http://localhost/mod.js",
+          "",
+          &[("x-typescript-types", "./mod.d.ts")],
+        )
+        .add_remote_file("http://localhost/mod.d.ts", "");
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  let mut files = result
+    .main
+    .files
+    .iter()
+    .map(|file| {
+      (
+        file.original_specifier.as_deref().unwrap(),
+        file.media_type,
+        file.kind,
+      )
+    })
+    .collect::<Vec<_>>();
+  files.sort_by(|a, b| a.0.cmp(b.0));
+  assert_eq!(
+    files,
+    vec![
+      (
+        "file:///mod.ts",
+        MediaType::TypeScript,
+        OutputFileKind::Local
+      ),
+      (
+        "file:///types.d.ts",
+        MediaType::Dts,
+        OutputFileKind::Declaration
+      ),
+      (
+        "http://localhost/mod.d.ts",
+        MediaType::Dts,
+        OutputFileKind::Declaration
+      ),
+      (
+        "http://localhost/mod.js",
+        MediaType::JavaScript,
+        OutputFileKind::Remote
+      ),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_output_graph() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import 'http://localhost/mod.js';\n",
+            "import type { Type } from './types.ts';\n",
+            "import('./other.ts');\n",
+            "import 'http://localhost/sdk/mod.ts';",
+          ),
+        )
+        .add_local_file("/types.ts", "export type Type = string;")
+        .add_local_file("/other.ts", "")
+        .add_remote_file_with_headers(
+          "http://localhost/mod.js",
+          "",
+          &[("x-typescript-types", "./mod.d.ts")],
+        )
+        .add_remote_file("http://localhost/mod.d.ts", "");
+    })
+    .add_external("http://localhost/sdk/", None)
+    .transform()
+    .await
+    .unwrap();
+
+  let graph = result.graph;
+  assert_eq!(
+    graph
+      .mappings
+      .iter()
+      .map(|(specifier, file_path)| (specifier.as_str(), file_path.clone()))
+      .collect::<Vec<_>>(),
+    vec![
+      ("file:///mod.ts", PathBuf::from("mod.ts")),
+      ("file:///other.ts", PathBuf::from("other.ts")),
+      ("file:///types.ts", PathBuf::from("types.ts")),
+      (
+        "http://localhost/mod.d.ts",
+        PathBuf::from("deps/0/mod.d.ts")
+      ),
+      ("http://localhost/mod.js", PathBuf::from("deps/0/mod.js")),
+    ]
+  );
+  let mod_module = graph
+    .modules
+    .iter()
+    .find(|module| module.specifier == "file:///mod.ts")
+    .unwrap();
+  assert_eq!(
+    mod_module.dependencies,
+    vec![
+      OutputDependency {
+        specifier: "http://localhost/mod.js".to_string(),
+        rewritten_specifier: "./deps/0/mod".to_string(),
+        resolved_specifier: Some("http://localhost/mod.js".to_string()),
+        kind: OutputDependencyKind::Static,
+      },
+      OutputDependency {
+        specifier: "./types.ts".to_string(),
+        rewritten_specifier: "./types".to_string(),
+        resolved_specifier: Some("file:///types.ts".to_string()),
+        kind: OutputDependencyKind::Types,
+      },
+      OutputDependency {
+        specifier: "http://localhost/sdk/mod.ts".to_string(),
+        rewritten_specifier: "http://localhost/sdk/mod.ts".to_string(),
+        resolved_specifier: Some("http://localhost/sdk/mod.ts".to_string()),
+        kind: OutputDependencyKind::Static,
+      },
+      OutputDependency {
+        specifier: "./other.ts".to_string(),
+        rewritten_specifier: "./other".to_string(),
+        resolved_specifier: Some("file:///other.ts".to_string()),
+        kind: OutputDependencyKind::Dynamic,
+      },
+    ]
+  );
+  assert_eq!(
+    graph.types,
+    vec![OutputTypesDependency {
+      specifier: "http://localhost/mod.js".to_string(),
+      types_specifier: "http://localhost/mod.d.ts".to_string(),
+    }]
+  );
+}
+
+#[tokio::test]
+async fn transform_ignored_dependencies() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "// dnt-ignore-next-line\n",
+            "import 'http://localhost/mod.ts';\n",
+            "import './other.ts';",
+          ),
+        )
+        .add_local_file(
+          "/other.ts",
+          "// dnt-ignore-file\nimport 'http://localhost/mod.ts';",
+        )
+        .add_remote_file("http://localhost/mod.ts", "");
+    })
+    .transform()
+    .await
+    .unwrap();
+
+  let get_dependencies = |specifier: &str| {
+    result
+      .graph
+      .modules
+      .iter()
+      .find(|module| module.specifier == specifier)
+      .unwrap()
+      .dependencies
+      .clone()
+  };
+  let ignored_dependency = OutputDependency {
+    specifier: "http://localhost/mod.ts".to_string(),
+    rewritten_specifier: "http://localhost/mod.ts".to_string(),
+    resolved_specifier: Some("http://localhost/mod.ts".to_string()),
+    kind: OutputDependencyKind::Static,
+  };
+  assert_eq!(
+    get_dependencies("file:///mod.ts"),
+    vec![
+      ignored_dependency.clone(),
+      OutputDependency {
+        specifier: "./other.ts".to_string(),
+        rewritten_specifier: "./other".to_string(),
+        resolved_specifier: Some("file:///other.ts".to_string()),
+        kind: OutputDependencyKind::Static,
+      },
+    ]
+  );
+  assert_eq!(
+    get_dependencies("file:///other.ts"),
+    vec![ignored_dependency]
+  );
+}
+
+#[tokio::test]
+async fn transform_graph_json_and_dot() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import 'http://localhost/mod.ts';\n",
+            "import 'http://localhost/sdk/mod.ts';\n",
+            "import('./other.ts');",
+          ),
+        )
+        .add_local_file("/other.ts", "import type { Type } from './mod.ts';")
+        .add_remote_file("http://localhost/mod.ts", "");
+    })
+    .add_external("http://localhost/sdk/", None)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_eq!(
+    get_graph_json_text(&result.graph),
+    concat!(
+      "{\n",
+      "  \"nodes\": [\n",
+      "    { \"specifier\": \"file:///mod.ts\", \"filePath\": \"mod.ts\" },\n",
+      "    { \"specifier\": \"file:///other.ts\", \"filePath\": \"other.ts\" },\n",
+      "    { \"specifier\": \"http://localhost/mod.ts\", \"filePath\": \"deps/0/mod.ts\" },\n",
+      "    { \"specifier\": \"http://localhost/sdk/mod.ts\", \"filePath\": null }\n",
+      "  ],\n",
+      "  \"edges\": [\n",
+      "    { \"from\": \"file:///mod.ts\", \"to\": \"file:///other.ts\", \"kind\": \"dynamic\" },\n",
+      "    { \"from\": \"file:///mod.ts\", \"to\": \"http://localhost/mod.ts\", \"kind\": \"static\" },\n",
+      "    { \"from\": \"file:///mod.ts\", \"to\": \"http://localhost/sdk/mod.ts\", \"kind\": \"static\" },\n",
+      "    { \"from\": \"file:///other.ts\", \"to\": \"file:///mod.ts\", \"kind\": \"types\" }\n",
+      "  ]\n",
+      "}\n",
+    )
+  );
+  assert_eq!(
+    get_graph_dot_text(&result.graph),
+    concat!(
+      "digraph {\n",
+      "  \"file:///mod.ts\" [label=\"file:///mod.ts\\nmod.ts\"];\n",
+      "  \"file:///other.ts\" [label=\"file:///other.ts\\nother.ts\"];\n",
+      "  \"http://localhost/mod.ts\" [label=\"http://localhost/mod.ts\\ndeps/0/mod.ts\"];\n",
+      "  \"http://localhost/sdk/mod.ts\" [label=\"http://localhost/sdk/mod.ts\\n(external)\"];\n",
+      "  \"file:///mod.ts\" -> \"file:///other.ts\" [style=dashed, label=\"dynamic\"];\n",
+      "  \"file:///mod.ts\" -> \"http://localhost/mod.ts\";\n",
+      "  \"file:///mod.ts\" -> \"http://localhost/sdk/mod.ts\";\n",
+      "  \"file:///other.ts\" -> \"file:///mod.ts\" [style=dotted, label=\"types\"];\n",
+      "}\n",
+    )
+  );
+}
+
+#[tokio::test]
+async fn transform_third_party_notices() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import 'http://localhost/a/mod.ts';\n",
+            "import 'http://otherhost/b/mod.ts';",
+          ),
+        )
+        .add_remote_file(
+          "http://localhost/a/mod.ts",
+          concat!(
+            "/**\n",
+            " * Copyright 2021 the authors.\n",
+            " * MIT License\n",
+            " */\n",
+            "import './other.ts';",
+          ),
+        )
+        .add_remote_file(
+          "http://localhost/a/other.ts",
+          "export {};\n// Copyright is not at the top",
+        )
+        .add_remote_file("http://localhost/LICENSE", "MIT License\n\n...\n")
+        .add_remote_file("http://otherhost/b/mod.ts", "// Some comment.\n");
+    })
+    .third_party_notices(true)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_eq!(
+    result.third_party_licenses,
+    vec![
+      RemoteLicense {
+        root: "http://localhost/".to_string(),
+        dir_path: PathBuf::from("deps/0"),
+        comments: vec![LicenseComment {
+          specifier: "http://localhost/a/mod.ts".to_string(),
+          text: "Copyright 2021 the authors.\nMIT License".to_string(),
+        }],
+        license_file_text: Some("MIT License\n\n...\n".to_string()),
+      },
+      RemoteLicense {
+        root: "http://otherhost/".to_string(),
+        dir_path: PathBuf::from("deps/1"),
+        comments: Vec::new(),
+        license_file_text: None,
+      },
+    ]
+  );
+  let notices_file = result
+    .main
+    .files
+    .iter()
+    .find(|file| file.file_path == PathBuf::from("THIRD_PARTY_NOTICES"))
+    .unwrap();
+  assert_eq!(
+    notices_file.file_text,
+    format!(
+      concat!(
+        "THIRD PARTY NOTICES\n\n",
+        "This package includes code from the following third party sources.\n",
+        "\n{0}\nhttp://localhost/ (deps/0)\n{0}\n",
+        "\nMIT License\n\n...\n",
+        "\nhttp://localhost/a/mod.ts:\n\n",
+        "Copyright 2021 the authors.\nMIT License\n",
+        "\n{0}\nhttp://otherhost/ (deps/1)\n{0}\n",
+        "\nNo license information found.\n",
+      ),
+      "-".repeat(72),
+    )
+  );
+}
+
+#[tokio::test]
+async fn transform_third_party_notices_parent_license_file() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "import 'http://localhost/mod@1.0.0/src/mod.ts';")
+        .add_remote_file("http://localhost/mod@1.0.0/src/mod.ts", "export {};")
+        .add_remote_file_with_error(
+          "http://localhost/mod@1.0.0/src/LICENSE",
+          "Error fetching http://localhost/mod@1.0.0/src/LICENSE (404 Not Found).",
+        )
+        .add_remote_file("http://localhost/mod@1.0.0/LICENSE", "MIT License")
+        .add_remote_file("http://localhost/LICENSE", "Other License");
+    })
+    .third_party_notices(true)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_eq!(
+    result.third_party_licenses,
+    vec![RemoteLicense {
+      root: "http://localhost/mod@1.0.0/src/".to_string(),
+      dir_path: PathBuf::from("deps/0"),
+      comments: Vec::new(),
+      license_file_text: Some("MIT License".to_string()),
+    }]
+  );
+}
+
+#[tokio::test]
+async fn transform_third_party_notices_host_license_file() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          concat!(
+            "import 'http://localhost/mod@1.0.0/src/mod.ts';\n",
+            "import 'https://localhost/lib/mod.ts';",
+          ),
+        )
+        .add_remote_file("http://localhost/mod@1.0.0/src/mod.ts", "export {};")
+        .add_remote_file("https://localhost/lib/mod.ts", "export {};")
+        .add_remote_file("http://localhost/LICENSE", "Other License")
+        .add_remote_file("https://localhost/LICENSE", "Other License");
+    })
+    .third_party_notices(true)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_eq!(
+    result.third_party_licenses,
+    vec![
+      RemoteLicense {
+        root: "http://localhost/mod@1.0.0/src/".to_string(),
+        dir_path: PathBuf::from("deps/0"),
+        comments: Vec::new(),
+        license_file_text: None,
+      },
+      RemoteLicense {
+        root: "https://localhost/lib/".to_string(),
+        dir_path: PathBuf::from("deps/1"),
+        comments: Vec::new(),
+        license_file_text: None,
+      },
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_source_headers() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          "Deno.cwd();\nimport 'http://localhost/mod.ts';",
+        )
+        .add_remote_file("http://localhost/mod.ts", "Deno.cwd();");
+    })
+    .source_headers(false)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      (
+        "mod.ts",
+        concat!(
+          "import * as denoShim from \"shim-package-name\";\n",
+          "denoShim.Deno.cwd();\n",
+          "import './deps/0/mod';",
+        )
+      ),
+      (
+        "deps/0/mod.ts",
+        concat!(
+          "// Source: http://localhost/mod.ts\n",
+          "import * as denoShim from \"shim-package-name\";\n",
+          "denoShim.Deno.cwd();",
+        )
+      ),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_source_headers_hash() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file("/mod.ts", "import 'http://localhost/mod.ts';")
+        .add_remote_file("http://localhost/mod.ts", "");
+    })
+    .source_headers(true)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      ("mod.ts", "import './deps/0/mod';"),
+      (
+        "deps/0/mod.ts",
+        concat!(
+          "// Source: http://localhost/mod.ts\n",
+          "// SHA-256: e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\n",
+        )
+      ),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn transform_source_headers_shebang() {
+  let result = TestBuilder::new()
+    .with_loader(|loader| {
+      loader
+        .add_local_file(
+          "/mod.ts",
+          "import 'http://localhost/a.js';\nimport 'http://localhost/b.js';",
+        )
+        .add_remote_file("http://localhost/a.js", "#!/usr/bin/env node\na();")
+        .add_remote_file("http://localhost/b.js", "#!/usr/bin/env node");
+    })
+    .source_headers(false)
+    .transform()
+    .await
+    .unwrap();
+
+  assert_files!(
+    result.main.files,
+    &[
+      ("mod.ts", "import './deps/0/a';\nimport './deps/0/b';"),
+      (
+        "deps/0/a.js",
+        concat!(
+          "#!/usr/bin/env node\n",
+          "// Source: http://localhost/a.js\n",
+          "a();",
+        )
+      ),
+      (
+        "deps/0/b.js",
+        concat!(
+          "#!/usr/bin/env node\n",
+          "// Source: http://localhost/b.js\n",
+        )
+      ),
+    ]
+  );
 }
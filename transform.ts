@@ -7,29 +7,189 @@ await init(getWasmLoadPromise());
 
 export interface TransformOptions {
   entryPoint: string | URL;
-  keepExtensions: boolean;
+  /** Extension to use in the rewritten specifiers of local and remote
+   * modules. "mjs" uses ".cjs" for CommonJS output. With "mjs" and "cjs",
+   * the output files are given matching extensions (ex. "mod.mts"), so
+   * JSX modules are not supported. Defaults to "js". */
+  specifierExtension?: "none" | "js" | "original" | "mjs" | "cjs";
+  /** Use "dual" to also output CommonJS modules. Defaults to "esm". */
+  moduleFormat?: "esm" | "dual";
+  /** How to handle `@deno-types` comments and triple-slash reference
+   * directives. Defaults to "rewrite". */
+  typeDirectives?: "remove" | "rewrite";
+  /** Package to import the Deno shim from. Ignored when `localShim`
+   * is true. */
   shimPackageName?: string;
+  /** Output a local `_dnt.shims.ts` module implementing the used Deno
+   * APIs instead of importing the shim package. Defaults to false. */
+  localShim?: boolean;
+  /** Use "named" to import the shim via a named import, which bundlers are
+   * able to tree shake. Defaults to "namespace". */
+  shimImportStyle?: "namespace" | "named";
+  /** Transforms the provided test modules and the modules only they
+   * depend on, rewriting `Deno.test` calls to use a test harness. */
+  test?: TestOptions;
+  /** Outputs the main modules as a single JavaScript module. */
+  bundle?: BundleOptions;
+  /** Modules that should not be loaded or output. */
+  externals?: External[];
+  /** Replacements for `Deno` member expression paths, which are used
+   * instead of the shim (ex. `{ "Deno.build.os": "process.platform" }`).
+   * Replacements that aren't simple expressions are parenthesized. */
+  defines?: { [path: string]: string };
+  /** `Deno` member paths supported by the shim (ex. "Deno.readTextFile"
+   * or "Deno.build"). When provided, transforming errors on any shimmed
+   * usage that isn't one of these or a member of one of these. */
+  supportedDenoApis?: string[];
+  /** Collects the licenses of the remote modules and outputs them
+   * to a `THIRD_PARTY_NOTICES` file. */
+  thirdPartyNotices?: ThirdPartyNoticesOptions;
+  /** Prepends a `// Source: <url>` comment to every remote module. */
+  sourceHeaders?: SourceHeaderOptions;
+}
+
+export interface ThirdPartyNoticesOptions {
+  /** Fetches the closest `LICENSE` file at or above the root url of each
+   * set of remote modules, stopping at the versioned package directory.
+   * Defaults to false. */
+  fetchLicenseFiles?: boolean;
+}
+
+export interface TestOptions {
+  entryPoints: (string | URL)[];
+  /** Named export of a module to use instead of the `test` function from
+   * `node:test`. It will be called with the same arguments as `Deno.test`. */
+  harness?: { module: string; exportName: string };
+  /** Module to use instead of the Deno standard library's
+   * `testing/asserts.ts` module. */
+  assertionModule?: string;
+}
+
+export interface BundleOptions {
+  /** Keeps imports of bare specifiers (ex. the assertion module) in the
+   * bundle. Otherwise, bundling errors when encountering one. The shim
+   * package import is always kept. Defaults to false. */
+  keepBareExternals?: boolean;
+}
+
+export interface External {
+  /** Prefix of the resolved specifiers to match (ex.
+   * "https://deno.land/x/sdk@1.0.0/" or "file:///project/generated.ts"). */
+  prefix: string;
+  /** Text to replace the matched prefix with in module specifiers
+   * (ex. "sdk/"). When not provided, the module specifiers are left as-is. */
+  replacement?: string;
+}
+
+export interface SourceHeaderOptions {
+  /** Also includes the SHA-256 hash of the original source text.
+   * Defaults to false. */
+  includeHash?: boolean;
 }
 
 export interface OutputFile {
   filePath: string;
   fileText: string;
+  /** Media type of the file (ex. "TypeScript" or "Dts"). */
+  mediaType: string;
+  kind: "local" | "remote" | "declaration";
+  /** Specifier of the module the file was output for. Not set for files
+   * generated by the transform, such as the local shim. */
+  originalSpecifier: string | null;
+}
+
+export interface TransformOutput {
+  main: TransformOutputEnvironment;
+  /** Modules only depended on by test entry points. */
+  test: TransformOutputEnvironment;
+  /** The Deno APIs used by each module that reference the shim. */
+  denoApiUsage: DenoApiUsage[];
+  /** The output modules and how they depend on each other. */
+  graph: OutputGraph;
+  /** License information of the remote modules. Empty unless the third
+   * party notices are output. */
+  thirdPartyLicenses: RemoteLicense[];
+}
+
+export interface RemoteLicense {
+  /** Root url of the remote modules. */
+  root: string;
+  /** Output directory of the remote modules (ex. "deps/0"). */
+  dirPath: string;
+  comments: { specifier: string; text: string }[];
+  licenseFileText: string | null;
+}
+
+export interface OutputGraph {
+  /** Output file path of each module specifier. */
+  mappings: { [specifier: string]: string };
+  modules: OutputModule[];
+  /** Modules along with the declaration file that provides their types. */
+  types: OutputTypesDependency[];
+}
+
+export interface OutputModule {
+  specifier: string;
+  filePath: string;
+  dependencies: OutputDependency[];
+}
+
+export interface OutputDependency {
+  /** Specifier text found in the original source. */
+  specifier: string;
+  /** Specifier text in the output. */
+  rewrittenSpecifier: string;
+  resolvedSpecifier: string | null;
+  kind: "static" | "dynamic" | "types";
+}
+
+export interface OutputTypesDependency {
+  specifier: string;
+  typesSpecifier: string;
+}
+
+export interface DenoApiUsage {
+  specifier: string;
+  filePath: string;
+  members: DenoMemberUsage[];
+}
+
+export interface DenoMemberUsage {
+  /** Member expression path (ex. `Deno.readTextFile`). */
+  path: string;
+  line: number;
+  column: number;
+}
+
+export interface TransformOutputEnvironment {
+  files: OutputFile[];
+  /** CommonJS versions of `files`. Empty unless the module format is "dual". */
+  cjsFiles: OutputFile[];
 }
 
 /** Analyzes the provided entry point to get all the dependended on modules and
  * outputs canonical TypeScript code in memory. The output of this function
  * can then be sent to the TypeScript compiler or a bundler for further processing. */
-export function transform(options: TransformOptions): Promise<OutputFile[]> {
+export function transform(
+  options: TransformOptions,
+): Promise<TransformOutput> {
   const newOptions = {
     ...options,
+    entryPoint: specifierToUrl(options.entryPoint),
+    test: options.test && {
+      ...options.test,
+      entryPoints: options.test.entryPoints.map(specifierToUrl),
+    },
   };
-  if (newOptions.entryPoint instanceof URL) {
-    newOptions.entryPoint = newOptions.entryPoint.toString();
+  return wasmFuncs.transform(newOptions);
+}
+
+function specifierToUrl(specifier: string | URL) {
+  if (specifier instanceof URL) {
+    return specifier.toString();
   } else {
-    newOptions.entryPoint = path.toFileUrl(path.resolve(newOptions.entryPoint))
-      .toString();
+    return path.toFileUrl(path.resolve(specifier)).toString();
   }
-  return wasmFuncs.transform(newOptions);
 }
 
 async function getWasmLoadPromise() {
@@ -2,6 +2,7 @@
 
 mod utils;
 
+use std::collections::HashMap;
 use std::future::Future;
 use std::path::PathBuf;
 
@@ -57,6 +58,9 @@ impl dnt::Loader for JsLoader {
         .unwrap();
       assert!(resp_value.is_instance_of::<Response>());
       let resp: Response = resp_value.dyn_into().unwrap();
+      if !resp.ok() {
+        anyhow::bail!("Error fetching {} ({}).", url, resp.status());
+      }
       let text = JsFuture::from(resp.text().unwrap()).await.unwrap();
       Ok(dnt::LoadResponse {
         content: text.as_string().unwrap(),
@@ -70,8 +74,52 @@ impl dnt::Loader for JsLoader {
 #[serde(rename_all = "camelCase")]
 pub struct TransformOptions {
   pub entry_point: String,
-  pub keep_extensions: bool,
+  pub specifier_extension: Option<dnt::SpecifierExtension>,
+  pub module_format: Option<dnt::ModuleFormat>,
+  pub type_directives: Option<dnt::TypeDirectiveMode>,
   pub shim_package_name: Option<String>,
+  pub local_shim: Option<bool>,
+  pub shim_import_style: Option<dnt::ShimImportStyle>,
+  pub test: Option<TestOptions>,
+  pub bundle: Option<BundleOptions>,
+  pub externals: Option<Vec<dnt::External>>,
+  pub defines: Option<HashMap<String, String>>,
+  pub supported_deno_apis: Option<Vec<String>>,
+  pub third_party_notices: Option<ThirdPartyNoticesOptions>,
+  pub source_headers: Option<SourceHeaderOptions>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestOptions {
+  pub entry_points: Vec<String>,
+  pub harness: Option<CustomTestHarness>,
+  pub assertion_module: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomTestHarness {
+  pub module: String,
+  pub export_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleOptions {
+  pub keep_bare_externals: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThirdPartyNoticesOptions {
+  pub fetch_license_files: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceHeaderOptions {
+  pub include_hash: Option<bool>,
 }
 
 #[wasm_bindgen]
@@ -82,12 +130,86 @@ pub async fn transform(options: JsValue) -> Result<JsValue, JsValue> {
 
   let result = dnt::transform(dnt::TransformOptions {
     entry_point: dnt::ModuleSpecifier::parse(&options.entry_point).unwrap(),
-    keep_extensions: options.keep_extensions,
-    shim_package_name: options.shim_package_name,
+    specifier_extension: options.specifier_extension.unwrap_or_default(),
+    module_format: options.module_format.unwrap_or(dnt::ModuleFormat::Esm),
+    type_directives: options
+      .type_directives
+      .unwrap_or(dnt::TypeDirectiveMode::Rewrite),
+    shim: if options.local_shim.unwrap_or(false) {
+      dnt::Shim::Local
+    } else {
+      dnt::Shim::Package(
+        options
+          .shim_package_name
+          .unwrap_or_else(|| "shim-package-name".to_string()),
+      )
+    },
+    shim_import_style: options
+      .shim_import_style
+      .unwrap_or(dnt::ShimImportStyle::Namespace),
     loader: Some(Box::new(JsLoader {})),
+    test: options.test.map(|test| dnt::TestOptions {
+      entry_points: test
+        .entry_points
+        .iter()
+        .map(|entry_point| dnt::ModuleSpecifier::parse(entry_point).unwrap())
+        .collect(),
+      harness: match test.harness {
+        Some(harness) => dnt::TestHarness::Custom {
+          module: harness.module,
+          export_name: harness.export_name,
+        },
+        None => dnt::TestHarness::NodeTest,
+      },
+      assertion_module: test.assertion_module,
+    }),
+    bundle: options.bundle.map(|bundle| dnt::BundleOptions {
+      keep_bare_externals: bundle.keep_bare_externals.unwrap_or(false),
+    }),
+    types_overrides: Default::default(),
+    defines: options.defines.unwrap_or_default(),
+    supported_deno_apis: options.supported_deno_apis,
+    externals: options.externals.unwrap_or_default(),
+    visitors: Vec::new(),
+    third_party_notices: options.third_party_notices.map(|notices| {
+      dnt::ThirdPartyNoticesOptions {
+        fetch_license_files: notices.fetch_license_files.unwrap_or(false),
+      }
+    }),
+    source_headers: options.source_headers.map(|headers| {
+      dnt::SourceHeaderOptions {
+        include_hash: headers.include_hash.unwrap_or(false),
+      }
+    }),
   })
   .await
   .unwrap();
 
   Ok(JsValue::from_serde(&result).unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  #[test]
+  fn serializes_output_file() {
+    let output_file = dnt::OutputFile {
+      file_path: PathBuf::from("mod.d.ts"),
+      file_text: "export {};".to_string(),
+      media_type: dnt::deno_ast::MediaType::Dts,
+      kind: dnt::OutputFileKind::Declaration,
+      original_specifier: Some("file:///mod.d.ts".to_string()),
+    };
+    assert_eq!(
+      serde_json::to_value(&output_file).unwrap(),
+      serde_json::json!({
+        "filePath": "mod.d.ts",
+        "fileText": "export {};",
+        "mediaType": "Dts",
+        "kind": "declaration",
+        "originalSpecifier": "file:///mod.d.ts",
+      })
+    );
+  }
+}